@@ -1,62 +1,170 @@
+pub mod cigar;
+pub mod flag;
 pub mod parser;
+pub mod position;
 pub mod reader;
+pub mod sequence;
+pub mod tag;
+pub mod writer;
+
+use cigar::Cigar;
+pub(crate) use flag::Flag;
+use position::Position;
+use sequence::{Quality, Sequence};
+use tag::{Tag, TagValue};
 
 #[derive(Debug)]
 pub(crate) struct Alignment {
     query_name: String,
     flag: Flag,
+    // `*` when unavailable
     ref_seq_name: String,
-    pos: u32,
+    pos: Option<Position>,
+    // 255 when unavailable
     map_quality: u8,
-    cigar: String,
+    cigar: Cigar,
+    // `=` for the same reference as RNAME, `*` when unavailable
     rnext: String,
-    pnext: u32,
+    pnext: Option<Position>,
     template_len: i32,
-    sequence: String,
-    phred_quality: String,
+    sequence: Sequence,
+    // `None` for `*`
+    quality: Option<Quality>,
+    tags: Vec<Tag>,
 }
 
-#[derive(Debug)]
-struct Flag(u16);
+impl Alignment {
+    /// An unmapped record with no reference position, as read from FASTQ.
+    pub(crate) fn unmapped(
+        query_name: String,
+        flag: Flag,
+        sequence: Sequence,
+        quality: Option<Quality>,
+    ) -> Self {
+        Self {
+            query_name,
+            flag,
+            ref_seq_name: "*".into(),
+            pos: None,
+            map_quality: 0,
+            cigar: Cigar::default(),
+            rnext: "*".into(),
+            pnext: None,
+            template_len: 0,
+            sequence,
+            quality,
+            tags: Vec::new(),
+        }
+    }
+
+    pub(crate) fn query_name(&self) -> &str {
+        &self.query_name
+    }
 
-impl Flag {
-    const fn has_multiple_segments(&self) -> bool {
-        self.0 & 0x1 > 0
+    pub(crate) fn flag(&self) -> &Flag {
+        &self.flag
     }
-    const fn each_seg_aligned(&self) -> bool {
-        self.0 & 0x2 > 0
+
+    /// RNAME as written, `*` if unavailable.
+    pub(crate) fn ref_seq_name(&self) -> &str {
+        &self.ref_seq_name
     }
-    const fn is_unmapped(&self) -> bool {
-        self.0 & 0x4 > 0
+
+    /// RNAME, `None` for `*`.
+    pub(crate) fn reference_name(&self) -> Option<&str> {
+        (self.ref_seq_name != "*").then_some(self.ref_seq_name.as_str())
     }
-    const fn next_is_unmapped(&self) -> bool {
-        self.0 & 0x8 > 0
+
+    /// Leftmost mapping position, `None` for POS 0.
+    pub(crate) fn position(&self) -> Option<Position> {
+        self.pos
     }
-    const fn is_reverse_complement(&self) -> bool {
-        self.0 & 0x10 > 0
+
+    /// MAPQ, `None` for 255.
+    pub(crate) fn mapping_quality(&self) -> Option<u8> {
+        (self.map_quality != 255).then_some(self.map_quality)
     }
-    const fn next_is_reverse_complement(&self) -> bool {
-        self.0 & 0x20 > 0
+
+    pub(crate) fn cigar(&self) -> &Cigar {
+        &self.cigar
     }
-    const fn is_first_segment(&self) -> bool {
-        self.0 & 0x40 > 0
+
+    /// Reference name of the next segment, `=` if it is the same as RNAME.
+    pub(crate) fn rnext(&self) -> &str {
+        &self.rnext
     }
-    const fn is_last_segment(&self) -> bool {
-        self.0 & 0x80 > 0
+
+    /// Reference name of the next segment with `=` resolved to RNAME, `None` for `*`.
+    pub(crate) fn mate_reference_name(&self) -> Option<&str> {
+        match self.rnext.as_str() {
+            "*" => None,
+            "=" => self.reference_name(),
+            name => Some(name),
+        }
     }
-    const fn is_secondary_alignment(&self) -> bool {
-        self.0 & 0x100 > 0
+
+    /// Leftmost position of the next segment, `None` for PNEXT 0.
+    pub(crate) fn mate_position(&self) -> Option<Position> {
+        self.pnext
     }
-    const fn not_passing_filters(&self) -> bool {
-        self.0 & 0x200 > 0
+
+    /// `true` if the next segment is placed on the same reference as this one.
+    pub(crate) fn mate_on_same_reference(&self) -> bool {
+        self.reference_name()
+            .is_some_and(|name| self.mate_reference_name() == Some(name))
     }
-    const fn is_duplicate(&self) -> bool {
-        self.0 & 0x400 > 0
+
+    /// SEQ, empty when absent.
+    pub(crate) fn sequence(&self) -> &Sequence {
+        &self.sequence
     }
-    const fn is_supplementary_alignment(&self) -> bool {
-        self.0 & 0x800 > 0
+
+    pub(crate) fn quality(&self) -> Option<&Quality> {
+        self.quality.as_ref()
     }
-    const fn is_primary_line(&self) -> bool {
-        self.0 & 0x900 == 0
+
+    pub(crate) fn flag_mut(&mut self) -> &mut Flag {
+        &mut self.flag
+    }
+
+    /// Places the alignment at `pos` on `ref_seq_name`, as done for unmapped reads with a mapped
+    /// mate.
+    pub(crate) fn set_position(&mut self, ref_seq_name: String, pos: Option<Position>) {
+        self.ref_seq_name = ref_seq_name;
+        self.pos = pos;
+    }
+
+    /// Rightmost reference position, the position itself if the CIGAR covers no reference and
+    /// `None` if unplaced. A CIGAR running past [`Position::MAX`] ends there.
+    pub(crate) fn end(&self) -> Option<Position> {
+        let span = self.cigar.reference_len().saturating_sub(1);
+        Position::new(self.pos?.get().saturating_add(span).min(Position::MAX))
+    }
+
+    pub(crate) fn template_len(&self) -> i32 {
+        self.template_len
+    }
+
+    pub(crate) fn set_mate(&mut self, rnext: String, pnext: Option<Position>, template_len: i32) {
+        self.rnext = rnext;
+        self.pnext = pnext;
+        self.template_len = template_len;
+    }
+
+    pub(crate) fn tag(&self, name: &[u8; 2]) -> Option<&Tag> {
+        self.tags.iter().find(|t| &t.name == name)
+    }
+
+    /// Replaces the value of the tag `name`, adding it at the end if absent.
+    pub(crate) fn set_tag(&mut self, name: [u8; 2], value: TagValue) {
+        match self.tags.iter_mut().find(|t| t.name == name) {
+            Some(tag) => tag.value = value,
+            None => self.tags.push(Tag { name, value }),
+        }
+    }
+
+    pub(crate) fn remove_tag(&mut self, name: &[u8; 2]) -> Option<Tag> {
+        let i = self.tags.iter().position(|t| &t.name == name)?;
+        Some(self.tags.remove(i))
     }
 }
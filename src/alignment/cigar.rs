@@ -0,0 +1,318 @@
+use std::{fmt, str::FromStr};
+
+use crate::header::parser::ParseError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Cigar(Vec<CigarOp>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CigarOp {
+    pub(crate) kind: CigarOpKind,
+    pub(crate) len: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CigarOpKind {
+    // M
+    Match,
+    // I
+    Insertion,
+    // D
+    Deletion,
+    // N
+    Skip,
+    // S
+    SoftClip,
+    // H
+    HardClip,
+    // P
+    Padding,
+    // =
+    SequenceMatch,
+    // X
+    SequenceMismatch,
+}
+
+impl CigarOpKind {
+    const fn from_code(c: u8) -> Option<Self> {
+        match c {
+            b'M' => Some(Self::Match),
+            b'I' => Some(Self::Insertion),
+            b'D' => Some(Self::Deletion),
+            b'N' => Some(Self::Skip),
+            b'S' => Some(Self::SoftClip),
+            b'H' => Some(Self::HardClip),
+            b'P' => Some(Self::Padding),
+            b'=' => Some(Self::SequenceMatch),
+            b'X' => Some(Self::SequenceMismatch),
+            _ => None,
+        }
+    }
+
+    pub(crate) const fn code(&self) -> u8 {
+        match self {
+            Self::Match => b'M',
+            Self::Insertion => b'I',
+            Self::Deletion => b'D',
+            Self::Skip => b'N',
+            Self::SoftClip => b'S',
+            Self::HardClip => b'H',
+            Self::Padding => b'P',
+            Self::SequenceMatch => b'=',
+            Self::SequenceMismatch => b'X',
+        }
+    }
+
+    pub(crate) const fn consumes_query(&self) -> bool {
+        matches!(
+            self,
+            Self::Match
+                | Self::Insertion
+                | Self::SoftClip
+                | Self::SequenceMatch
+                | Self::SequenceMismatch
+        )
+    }
+
+    pub(crate) const fn consumes_reference(&self) -> bool {
+        matches!(
+            self,
+            Self::Match
+                | Self::Deletion
+                | Self::Skip
+                | Self::SequenceMatch
+                | Self::SequenceMismatch
+        )
+    }
+}
+
+impl Cigar {
+    pub(crate) fn ops(&self) -> &[CigarOp] {
+        &self.0
+    }
+
+    /// Number of reference bases covered by the alignment, including deletions and skips.
+    pub(crate) fn reference_len(&self) -> u32 {
+        self.0
+            .iter()
+            .filter(|op| op.kind.consumes_reference())
+            .map(|op| op.len)
+            .sum()
+    }
+
+    /// Length of the soft and hard clips at the start of the alignment.
+    pub(crate) fn leading_clips(&self) -> u32 {
+        clip_len(self.0.iter())
+    }
+
+    /// Length of the soft and hard clips at the end of the alignment.
+    pub(crate) fn trailing_clips(&self) -> u32 {
+        clip_len(self.0.iter().rev())
+    }
+
+    /// Number of bases in SEQ implied by the CIGAR.
+    pub(crate) fn query_len(&self) -> u32 {
+        self.0
+            .iter()
+            .filter(|op| op.kind.consumes_query())
+            .map(|op| op.len)
+            .sum()
+    }
+}
+
+fn clip_len<'a>(ops: impl Iterator<Item = &'a CigarOp>) -> u32 {
+    ops.take_while(|op| matches!(op.kind, CigarOpKind::SoftClip | CigarOpKind::HardClip))
+        .map(|op| op.len)
+        .sum()
+}
+
+impl FromStr for Cigar {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "*" {
+            return Ok(Self::default());
+        }
+
+        let mut ops = Vec::new();
+        let mut len: Option<u32> = None;
+        for c in s.bytes() {
+            if c.is_ascii_digit() {
+                let digit = u32::from(c - b'0');
+                len = Some(
+                    len.unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|l| l.checked_add(digit))
+                        .ok_or(ParseError::BadCigar)?,
+                );
+            } else {
+                let kind = CigarOpKind::from_code(c).ok_or(ParseError::BadCigar)?;
+                let len = len.take().ok_or(ParseError::BadCigar)?;
+                ops.push(CigarOp { kind, len });
+            }
+        }
+        if len.is_some() || ops.is_empty() {
+            return Err(ParseError::BadCigar);
+        }
+        Ok(Self(ops))
+    }
+}
+
+/// A single base of an alignment as described by the CIGAR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AlignedPosition {
+    pub(crate) kind: CigarOpKind,
+    /// 1-based reference position, `None` for operations which don't consume the reference
+    pub(crate) ref_pos: Option<u32>,
+    /// 0-based offset into SEQ, `None` for operations which don't consume the query
+    pub(crate) query_pos: Option<u32>,
+}
+
+/// Walks every query and reference base of a CIGAR, skipping hard clips and padding.
+pub(crate) struct AlignedPositions<'a> {
+    ops: std::slice::Iter<'a, CigarOp>,
+    current: Option<CigarOp>,
+    ref_pos: u32,
+    query_pos: u32,
+}
+
+impl Cigar {
+    /// Iterates over the bases of an alignment starting at the 1-based reference position `pos`.
+    pub(crate) fn aligned_positions(&self, pos: u32) -> AlignedPositions<'_> {
+        AlignedPositions {
+            ops: self.0.iter(),
+            current: None,
+            ref_pos: pos,
+            query_pos: 0,
+        }
+    }
+}
+
+impl Iterator for AlignedPositions<'_> {
+    type Item = AlignedPosition;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let op = loop {
+            match self.current {
+                Some(op) if op.len > 0 => break op,
+                _ => {
+                    let op = *self.ops.next()?;
+                    if !matches!(op.kind, CigarOpKind::HardClip | CigarOpKind::Padding) {
+                        self.current = Some(op);
+                    }
+                }
+            }
+        };
+
+        let ref_pos = op.kind.consumes_reference().then_some(self.ref_pos);
+        let query_pos = op.kind.consumes_query().then_some(self.query_pos);
+        self.ref_pos += u32::from(ref_pos.is_some());
+        self.query_pos += u32::from(query_pos.is_some());
+        self.current = Some(CigarOp {
+            kind: op.kind,
+            len: op.len - 1,
+        });
+
+        Some(AlignedPosition {
+            kind: op.kind,
+            ref_pos,
+            query_pos,
+        })
+    }
+}
+
+impl fmt::Display for Cigar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("*");
+        }
+        for op in &self.0 {
+            write!(f, "{}{}", op.len, char::from(op.kind.code()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(cigar: &str, pos: u32) -> Vec<(CigarOpKind, Option<u32>, Option<u32>)> {
+        let cigar: Cigar = cigar.parse().unwrap();
+        cigar
+            .aligned_positions(pos)
+            .map(|p| (p.kind, p.ref_pos, p.query_pos))
+            .collect()
+    }
+
+    #[test]
+    fn parses_and_writes_operations() {
+        let cigar: Cigar = "5H3S10M2I4D100N6=1X2P".parse().unwrap();
+        assert_eq!(cigar.ops().len(), 9);
+        assert_eq!(cigar.to_string(), "5H3S10M2I4D100N6=1X2P");
+        assert_eq!(cigar.reference_len(), 10 + 4 + 100 + 6 + 1);
+        assert_eq!(cigar.query_len(), 3 + 10 + 2 + 6 + 1);
+        assert_eq!(cigar.leading_clips(), 8);
+        assert_eq!(cigar.trailing_clips(), 0);
+    }
+
+    #[test]
+    fn parses_missing_cigar() {
+        let cigar: Cigar = "*".parse().unwrap();
+        assert!(cigar.ops().is_empty());
+        assert_eq!(cigar.reference_len(), 0);
+        assert_eq!(cigar.to_string(), "*");
+    }
+
+    #[test]
+    fn rejects_malformed_cigars() {
+        for s in ["", "M", "10", "10M5", "10Q", "4294967296M", "-1M"] {
+            assert!(s.parse::<Cigar>().is_err(), "{s}");
+        }
+        assert!("4294967295M".parse::<Cigar>().is_ok());
+    }
+
+    #[test]
+    fn walks_matches_and_insertions() {
+        use CigarOpKind::*;
+        assert_eq!(
+            positions("2S2M1I1M", 10),
+            [
+                (SoftClip, None, Some(0)),
+                (SoftClip, None, Some(1)),
+                (Match, Some(10), Some(2)),
+                (Match, Some(11), Some(3)),
+                (Insertion, None, Some(4)),
+                (Match, Some(12), Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn walks_deletions_and_skips() {
+        use CigarOpKind::*;
+        assert_eq!(
+            positions("1M1D2N1=", 1),
+            [
+                (Match, Some(1), Some(0)),
+                (Deletion, Some(2), None),
+                (Skip, Some(3), None),
+                (Skip, Some(4), None),
+                (SequenceMatch, Some(5), Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_hard_clips_padding_and_empty_operations() {
+        use CigarOpKind::*;
+        assert_eq!(
+            positions("3H1M0M2P1X3H", 7),
+            [
+                (Match, Some(7), Some(0)),
+                (SequenceMismatch, Some(8), Some(1)),
+            ]
+        );
+        assert!(positions("*", 1).is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+use std::{fmt, str::FromStr};
+
+use crate::header::parser::ParseError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Flag(u16);
+
+/// samtools names of the flag bits, in bit order.
+pub(crate) const NAMES: [(u16, &str); 12] = [
+    (Flag::PAIRED, "PAIRED"),
+    (Flag::PROPER_PAIR, "PROPER_PAIR"),
+    (Flag::UNMAP, "UNMAP"),
+    (Flag::MUNMAP, "MUNMAP"),
+    (Flag::REVERSE, "REVERSE"),
+    (Flag::MREVERSE, "MREVERSE"),
+    (Flag::READ1, "READ1"),
+    (Flag::READ2, "READ2"),
+    (Flag::SECONDARY, "SECONDARY"),
+    (Flag::QCFAIL, "QCFAIL"),
+    (Flag::DUP, "DUP"),
+    (Flag::SUPPLEMENTARY, "SUPPLEMENTARY"),
+];
+
+/// A combination of bits the SAM specification rules out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlagProblem {
+    /// PROPER_PAIR, MUNMAP, MREVERSE, READ1 or READ2 without PAIRED
+    MateBitsWithoutPaired,
+    /// SECONDARY and SUPPLEMENTARY together
+    SecondaryAndSupplementary,
+    /// SECONDARY or SUPPLEMENTARY on an unmapped read
+    UnmappedNotPrimary,
+}
+
+impl FlagProblem {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::MateBitsWithoutPaired => "MATE_BITS_WITHOUT_PAIRED",
+            Self::SecondaryAndSupplementary => "SECONDARY_AND_SUPPLEMENTARY",
+            Self::UnmappedNotPrimary => "UNMAPPED_NOT_PRIMARY",
+        }
+    }
+}
+
+impl Flag {
+    pub(crate) const PAIRED: u16 = 0x1;
+    pub(crate) const PROPER_PAIR: u16 = 0x2;
+    pub(crate) const UNMAP: u16 = 0x4;
+    pub(crate) const MUNMAP: u16 = 0x8;
+    pub(crate) const REVERSE: u16 = 0x10;
+    pub(crate) const MREVERSE: u16 = 0x20;
+    pub(crate) const READ1: u16 = 0x40;
+    pub(crate) const READ2: u16 = 0x80;
+    pub(crate) const SECONDARY: u16 = 0x100;
+    pub(crate) const QCFAIL: u16 = 0x200;
+    pub(crate) const DUP: u16 = 0x400;
+    pub(crate) const SUPPLEMENTARY: u16 = 0x800;
+
+    pub(crate) const fn new(bits: u16) -> Self {
+        Self(bits)
+    }
+    pub(crate) const fn bits(&self) -> u16 {
+        self.0
+    }
+    /// Sets or clears all bits of `mask`.
+    pub(crate) const fn set(&mut self, mask: u16, value: bool) {
+        if value {
+            self.0 |= mask;
+        } else {
+            self.0 &= !mask;
+        }
+    }
+    pub(crate) const fn has_multiple_segments(&self) -> bool {
+        self.0 & Self::PAIRED != 0
+    }
+    pub(crate) const fn each_seg_aligned(&self) -> bool {
+        self.0 & Self::PROPER_PAIR != 0
+    }
+    pub(crate) const fn is_unmapped(&self) -> bool {
+        self.0 & Self::UNMAP != 0
+    }
+    pub(crate) const fn next_is_unmapped(&self) -> bool {
+        self.0 & Self::MUNMAP != 0
+    }
+    pub(crate) const fn is_reverse_complement(&self) -> bool {
+        self.0 & Self::REVERSE != 0
+    }
+    pub(crate) const fn next_is_reverse_complement(&self) -> bool {
+        self.0 & Self::MREVERSE != 0
+    }
+    pub(crate) const fn is_first_segment(&self) -> bool {
+        self.0 & Self::READ1 != 0
+    }
+    pub(crate) const fn is_last_segment(&self) -> bool {
+        self.0 & Self::READ2 != 0
+    }
+    pub(crate) const fn is_secondary_alignment(&self) -> bool {
+        self.0 & Self::SECONDARY != 0
+    }
+    pub(crate) const fn not_passing_filters(&self) -> bool {
+        self.0 & Self::QCFAIL != 0
+    }
+    pub(crate) const fn is_duplicate(&self) -> bool {
+        self.0 & Self::DUP != 0
+    }
+    pub(crate) const fn is_supplementary_alignment(&self) -> bool {
+        self.0 & Self::SUPPLEMENTARY != 0
+    }
+    pub(crate) const fn is_primary_line(&self) -> bool {
+        self.0 & (Self::SECONDARY | Self::SUPPLEMENTARY) == 0
+    }
+
+    pub(crate) fn builder() -> FlagBuilder {
+        FlagBuilder::default()
+    }
+
+    /// Combinations of bits that break the rules of the specification.
+    pub(crate) fn problems(&self) -> Vec<FlagProblem> {
+        let has = |mask: u16| self.0 & mask != 0;
+        let mate_bits =
+            Self::PROPER_PAIR | Self::MUNMAP | Self::MREVERSE | Self::READ1 | Self::READ2;
+        let mut problems = Vec::new();
+        if !has(Self::PAIRED) && has(mate_bits) {
+            problems.push(FlagProblem::MateBitsWithoutPaired);
+        }
+        if has(Self::SECONDARY) && has(Self::SUPPLEMENTARY) {
+            problems.push(FlagProblem::SecondaryAndSupplementary);
+        }
+        if has(Self::UNMAP) && has(Self::SECONDARY | Self::SUPPLEMENTARY) {
+            problems.push(FlagProblem::UnmappedNotPrimary);
+        }
+        problems
+    }
+}
+
+/// Writes the names of the set bits separated by commas, as `samtools flags`.
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = NAMES.iter().filter(|(mask, _)| self.0 & mask != 0);
+        if let Some((_, name)) = names.next() {
+            write!(f, "{name}")?;
+        }
+        for (_, name) in names {
+            write!(f, ",{name}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Flag {
+    type Err = ParseError;
+
+    /// Parses comma-separated names or numbers, decimal or `0x`-prefixed hexadecimal, e.g.
+    /// `PAIRED,READ1` or `0x41`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bits = 0;
+        for item in s.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            bits |= match NAMES
+                .iter()
+                .find(|(_, name)| item.eq_ignore_ascii_case(name))
+            {
+                Some((mask, _)) => *mask,
+                None => match item.strip_prefix("0x").or_else(|| item.strip_prefix("0X")) {
+                    Some(hex) => u16::from_str_radix(hex, 16),
+                    None => item.parse(),
+                }
+                .map_err(|_| ParseError::BadFlag)?,
+            };
+        }
+        Ok(Self(bits))
+    }
+}
+
+/// Builds a flag bit by bit, checking the result against the rules of the specification.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FlagBuilder(u16);
+
+impl FlagBuilder {
+    pub(crate) fn paired(self) -> Self {
+        Self(self.0 | Flag::PAIRED)
+    }
+
+    pub(crate) fn proper_pair(self) -> Self {
+        Self(self.0 | Flag::PROPER_PAIR)
+    }
+
+    pub(crate) fn unmapped(self) -> Self {
+        Self(self.0 | Flag::UNMAP)
+    }
+
+    pub(crate) fn mate_unmapped(self) -> Self {
+        Self(self.0 | Flag::MUNMAP)
+    }
+
+    pub(crate) fn reverse(self) -> Self {
+        Self(self.0 | Flag::REVERSE)
+    }
+
+    pub(crate) fn mate_reverse(self) -> Self {
+        Self(self.0 | Flag::MREVERSE)
+    }
+
+    pub(crate) fn read1(self) -> Self {
+        Self(self.0 | Flag::READ1)
+    }
+
+    pub(crate) fn read2(self) -> Self {
+        Self(self.0 | Flag::READ2)
+    }
+
+    pub(crate) fn secondary(self) -> Self {
+        Self(self.0 | Flag::SECONDARY)
+    }
+
+    pub(crate) fn qc_fail(self) -> Self {
+        Self(self.0 | Flag::QCFAIL)
+    }
+
+    pub(crate) fn duplicate(self) -> Self {
+        Self(self.0 | Flag::DUP)
+    }
+
+    pub(crate) fn supplementary(self) -> Self {
+        Self(self.0 | Flag::SUPPLEMENTARY)
+    }
+
+    pub(crate) fn build(self) -> Result<Flag, ParseError> {
+        let flag = Flag(self.0);
+        if flag.problems().is_empty() {
+            Ok(flag)
+        } else {
+            Err(ParseError::BadFlag)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> u16 {
+        s.parse::<Flag>().unwrap().bits()
+    }
+
+    #[test]
+    fn parses_names_and_numbers() {
+        assert_eq!(parse("PAIRED,READ1"), 0x41);
+        assert_eq!(parse("paired, Read2 ,"), 0x81);
+        assert_eq!(parse("0x900"), 0x900);
+        assert_eq!(parse("0X10,4"), 0x14);
+        assert_eq!(parse("1,PAIRED"), 0x1);
+        assert_eq!(parse(""), 0);
+    }
+
+    #[test]
+    fn rejects_unknown_names_and_out_of_range_numbers() {
+        for s in ["PAIRD", "65536", "-1", "0x", "0x10000", "1;2"] {
+            assert!(s.parse::<Flag>().is_err(), "{s}");
+        }
+        assert_eq!(parse("65535"), u16::MAX);
+    }
+
+    #[test]
+    fn writes_names_in_bit_order() {
+        assert_eq!(Flag::new(0x41).to_string(), "PAIRED,READ1");
+        assert_eq!(Flag::new(0x800 | 0x10).to_string(), "REVERSE,SUPPLEMENTARY");
+        assert_eq!(Flag::new(0).to_string(), "");
+        // Bits without a name are left out
+        assert_eq!(Flag::new(0x1000 | 0x4).to_string(), "UNMAP");
+        let all: Flag = Flag::new(0xfff).to_string().parse().unwrap();
+        assert_eq!(all.bits(), 0xfff);
+    }
+
+    #[test]
+    fn finds_problems() {
+        assert!(Flag::new(0x63).problems().is_empty());
+        assert_eq!(
+            Flag::new(0x40).problems(),
+            [FlagProblem::MateBitsWithoutPaired]
+        );
+        assert_eq!(
+            Flag::new(0x904).problems(),
+            [
+                FlagProblem::SecondaryAndSupplementary,
+                FlagProblem::UnmappedNotPrimary
+            ]
+        );
+    }
+
+    #[test]
+    fn builds_only_valid_flags() {
+        let flag = Flag::builder().paired().read1().qc_fail().build().unwrap();
+        assert_eq!(flag.bits(), 0x241);
+        assert!(Flag::builder().read2().build().is_err());
+        assert!(Flag::builder().secondary().supplementary().build().is_err());
+        assert!(Flag::builder().unmapped().secondary().build().is_err());
+    }
+
+    #[test]
+    fn builds_every_bit() {
+        let flag = Flag::builder()
+            .paired()
+            .proper_pair()
+            .mate_reverse()
+            .read2()
+            .reverse()
+            .duplicate()
+            .supplementary()
+            .build()
+            .unwrap();
+        assert_eq!(
+            flag.to_string(),
+            "PAIRED,PROPER_PAIR,REVERSE,MREVERSE,READ2,DUP,SUPPLEMENTARY"
+        );
+        let flag = Flag::builder()
+            .paired()
+            .unmapped()
+            .mate_unmapped()
+            .read1()
+            .qc_fail()
+            .build()
+            .unwrap();
+        assert_eq!(flag.bits(), 0x24d);
+        let secondary = Flag::builder().secondary().build().unwrap();
+        assert!(!secondary.is_primary_line());
+    }
+}
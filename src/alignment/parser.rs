@@ -1,5 +1,10 @@
 use crate::{
-    alignment::{Alignment, Flag},
+    alignment::{
+        Alignment, Flag,
+        cigar::Cigar,
+        position::parse_position,
+        sequence::{Quality, Sequence},
+    },
     header::parser::ParseError,
 };
 
@@ -21,35 +26,39 @@ pub(crate) fn parse_alignment(s: &[u8]) -> Result<Alignment, ParseError> {
 
     let query_name = next_field(&mut fields)?.to_owned();
     // FIXME: change error type
-    let flag = Flag(
+    let flag = Flag::new(
         next_field(&mut fields)?
             .parse()
             .map_err(|_| ParseError::UnknownValue)?,
     );
     let ref_seq_name = next_field(&mut fields)?.to_owned();
-    let pos = next_field(&mut fields)?
-        .parse()
-        .map_err(|_| ParseError::UnknownValue)?;
+    let pos = parse_position(next_field(&mut fields)?)?;
     let map_quality = next_field(&mut fields)?
         .parse()
         .map_err(|_| ParseError::UnknownValue)?;
-    let cigar = next_field(&mut fields)?.to_owned();
+    let cigar: Cigar = next_field(&mut fields)?.parse()?;
     let rnext = next_field(&mut fields)?.to_owned();
-    let pnext = next_field(&mut fields)?
-        .parse()
-        .map_err(|_| ParseError::UnknownValue)?;
+    let pnext = parse_position(next_field(&mut fields)?)?;
     let template_len = next_field(&mut fields)?
         .parse()
         .map_err(|_| ParseError::UnknownValue)?;
-    let sequence = next_field(&mut fields)?
-        .parse()
-        .map_err(|_| ParseError::UnknownValue)?;
-    let phred_quality = next_field(&mut fields)?.to_owned();
+    let sequence: Sequence = next_field(&mut fields)?.parse()?;
+    let quality: Option<Quality> = match next_field(&mut fields)? {
+        "*" => None,
+        q => Some(q.parse()?),
+    };
+    // SEQ, QUAL and the CIGAR must agree on the number of bases when present
+    if quality.as_ref().is_some_and(|q| q.len() != sequence.len())
+        || (!sequence.is_empty()
+            && !cigar.ops().is_empty()
+            && cigar.query_len() as usize != sequence.len())
+    {
+        return Err(ParseError::LengthMismatch);
+    }
 
-    // TODO: Parse and return other fields
-    // This may be challenging due to different fields having different data types
-    // NOTE: Can there be invalid UTF8 here?
-    let _other_fields: Vec<_> = fields.map(parse_str).collect::<Result<_, _>>()?;
+    let tags = fields
+        .map(|f| parse_str(f)?.parse())
+        .collect::<Result<_, _>>()?;
 
     Ok(Alignment {
         query_name,
@@ -62,7 +71,8 @@ pub(crate) fn parse_alignment(s: &[u8]) -> Result<Alignment, ParseError> {
         pnext,
         template_len,
         sequence,
-        phred_quality,
+        quality,
+        tags,
     })
 }
 
@@ -0,0 +1,91 @@
+use std::{fmt, num::NonZeroU32};
+
+use crate::header::parser::ParseError;
+
+/// A 1-based position on a reference sequence, as in POS and PNEXT.
+///
+/// 0-based offsets into sequences stay plain `usize`s; conversions between the two are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Position(NonZeroU32);
+
+impl Position {
+    /// The largest position SAM allows, 2^31 - 1.
+    pub(crate) const MAX: u32 = i32::MAX as u32;
+
+    /// The position `n`, `None` for 0 (unavailable) or past [`Position::MAX`].
+    pub(crate) fn new(n: u32) -> Option<Self> {
+        (n <= Self::MAX).then(|| NonZeroU32::new(n).map(Self))?
+    }
+
+    /// The position of the 0-based `offset`.
+    pub(crate) fn from_offset(offset: usize) -> Option<Self> {
+        Self::new(u32::try_from(offset.checked_add(1)?).ok()?)
+    }
+
+    pub(crate) fn get(self) -> u32 {
+        self.0.get()
+    }
+
+    /// The 0-based offset of this position.
+    pub(crate) fn to_offset(self) -> usize {
+        self.get() as usize - 1
+    }
+}
+
+/// Parses a POS or PNEXT field, giving `None` for 0.
+pub(crate) fn parse_position(s: &str) -> Result<Option<Position>, ParseError> {
+    let n: u32 = s.parse().map_err(|_| ParseError::UnknownValue)?;
+    match n {
+        0 => Ok(None),
+        n => Position::new(n)
+            .map(Some)
+            .ok_or(ParseError::PositionOutOfRange(n)),
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_offsets_and_positions() {
+        assert_eq!(Position::from_offset(0).map(Position::get), Some(1));
+        assert_eq!(Position::from_offset(41).unwrap().to_offset(), 41);
+        let max = Position::new(Position::MAX).unwrap();
+        assert_eq!(Position::from_offset(max.to_offset()), Some(max));
+    }
+
+    #[test]
+    fn rejects_offsets_past_the_largest_position() {
+        assert_eq!(Position::from_offset(Position::MAX as usize), None);
+        assert_eq!(Position::from_offset(usize::MAX), None);
+    }
+
+    #[test]
+    fn keeps_positions_in_range() {
+        assert_eq!(Position::new(0), None);
+        assert_eq!(Position::new(Position::MAX + 1), None);
+        assert_eq!(Position::new(7).map(|p| p.to_string()), Some("7".into()));
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(parse_position("0").unwrap(), None);
+        assert_eq!(
+            parse_position("2147483647").unwrap(),
+            Position::new(Position::MAX)
+        );
+        assert!(matches!(
+            parse_position("2147483648"),
+            Err(ParseError::PositionOutOfRange(2147483648))
+        ));
+        assert!(parse_position("-1").is_err());
+        assert!(parse_position("").is_err());
+    }
+}
@@ -15,6 +15,40 @@ pub fn read_alignments(r: &mut impl BufRead) -> Result<Vec<Alignment>, ParseErro
         .collect()
 }
 
+/// Lazily parses one alignment per line, skipping empty lines.
+pub(crate) struct Alignments<R> {
+    reader: R,
+    buf: String,
+}
+
+impl<R: BufRead> Alignments<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Alignments<R> {
+    type Item = Result<Alignment, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(_) => return Some(Err(ParseError::IOError)),
+            }
+            let line = self.buf.trim_end_matches(['\n', '\r']);
+            if !line.is_empty() {
+                return Some(read_alignment(line));
+            }
+        }
+    }
+}
+
 fn read_alignment(s: &str) -> Result<Alignment, ParseError> {
     parse_alignment(s.as_bytes())
 }
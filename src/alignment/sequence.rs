@@ -0,0 +1,265 @@
+use std::{fmt, str::FromStr};
+
+use crate::header::parser::ParseError;
+
+/// Complement of an IUPAC nucleotide code, preserving case.
+pub(crate) const fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' | b'u' => b'a',
+        b'r' => b'y',
+        b'y' => b'r',
+        b'k' => b'm',
+        b'm' => b'k',
+        b'b' => b'v',
+        b'v' => b'b',
+        b'd' => b'h',
+        b'h' => b'd',
+        // N, S, W and anything else are their own complement
+        other => other,
+    }
+}
+
+pub(crate) fn reverse_complement(bases: &[u8]) -> Vec<u8> {
+    bases.iter().rev().map(|&b| complement(b)).collect()
+}
+
+/// Base codes of the 4-bit packed representation used by BAM, in code order.
+const PACKED_BASES: &[u8; 16] = b"=ACMGRSVTWYHKDBN";
+
+/// SEQ of a record: bases from `[A-Za-z=.]`, empty when absent (`*`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Sequence(String);
+
+impl Sequence {
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn reverse_complement(&self) -> Self {
+        // Complements stay within the accepted characters, so this remains valid UTF-8
+        Self(String::from_utf8(reverse_complement(self.as_bytes())).unwrap_or_default())
+    }
+
+    /// Packs two bases per byte, the first in the high nibble, as in BAM. Lower case bases are
+    /// packed as upper case and anything without a code, such as `.`, as `N`.
+    pub(crate) fn to_packed(&self) -> Vec<u8> {
+        let code = |base: u8| {
+            PACKED_BASES
+                .iter()
+                .position(|&b| b == base.to_ascii_uppercase())
+                .unwrap_or(15) as u8
+        };
+        self.as_bytes()
+            .chunks(2)
+            .map(|pair| code(pair[0]) << 4 | pair.get(1).map_or(0, |&b| code(b)))
+            .collect()
+    }
+
+    /// Unpacks `len` bases packed by [`Sequence::to_packed`].
+    pub(crate) fn from_packed(packed: &[u8], len: usize) -> Self {
+        let bases = packed
+            .iter()
+            .flat_map(|&b| [b >> 4, b & 0xf])
+            .take(len)
+            .map(|code| char::from(PACKED_BASES[usize::from(code)]));
+        Self(bases.collect())
+    }
+}
+
+impl FromStr for Sequence {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "*" => Ok(Self::default()),
+            s if s
+                .bytes()
+                .all(|b| b.is_ascii_alphabetic() || b == b'=' || b == b'.') =>
+            {
+                Ok(Self(s.into()))
+            }
+            _ => Err(ParseError::BadSequence),
+        }
+    }
+}
+
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_str() {
+            "" => write!(f, "*"),
+            s => write!(f, "{s}"),
+        }
+    }
+}
+
+/// QUAL of a record as raw Phred scores; an absent QUAL (`*`) is `None` where it is stored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Quality(Vec<u8>);
+
+impl Quality {
+    pub(crate) fn new(scores: Vec<u8>) -> Self {
+        Self(scores)
+    }
+
+    /// Decodes qualities written with an offset, 33 for Sanger FASTQ and SAM or 64 for old
+    /// Illumina FASTQ.
+    pub(crate) fn from_ascii(encoded: &[u8], offset: u8) -> Result<Self, ParseError> {
+        encoded
+            .iter()
+            .map(|&c| match c.checked_sub(offset) {
+                Some(q) if c <= b'~' => Ok(q),
+                _ => Err(ParseError::BadQuality),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+    }
+
+    /// Encodes the scores with an offset, capping them to what stays printable.
+    pub(crate) fn to_ascii(&self, offset: u8) -> Vec<u8> {
+        self.0
+            .iter()
+            .map(|&q| q.min(b'~' - offset) + offset)
+            .collect()
+    }
+
+    pub(crate) fn scores(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Mean Phred score, `None` without scores.
+    pub(crate) fn mean(&self) -> Option<f64> {
+        let sum: u64 = self.0.iter().map(|&q| u64::from(q)).sum();
+        (!self.0.is_empty()).then(|| sum as f64 / self.0.len() as f64)
+    }
+
+    pub(crate) fn reversed(&self) -> Self {
+        Self(self.0.iter().rev().copied().collect())
+    }
+}
+
+impl FromStr for Quality {
+    type Err = ParseError;
+
+    /// Parses Phred+33 text; `*` must be handled by the caller as an absent QUAL.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_ascii(s.as_bytes(), 33)
+    }
+}
+
+/// Writes the scores as Phred+33 text.
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = self.to_ascii(33);
+        write!(f, "{}", String::from_utf8_lossy(&text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_qualities_with_an_offset() {
+        let quality = Quality::from_ascii(b"!+I~", 33).unwrap();
+        assert_eq!(quality.scores(), [0, 10, 40, 93]);
+        let quality = Quality::from_ascii(b"@Jh", 64).unwrap();
+        assert_eq!(quality.scores(), [0, 10, 40]);
+        assert!(Quality::from_ascii(b"", 33).unwrap().scores().is_empty());
+    }
+
+    #[test]
+    fn rejects_characters_outside_the_range() {
+        assert!(Quality::from_ascii(b" ", 33).is_err());
+        assert!(Quality::from_ascii(b"\x7f", 33).is_err());
+        assert!(Quality::from_ascii(b"II\xff", 33).is_err());
+        // Sanger qualities below 31 are not valid with an offset of 64
+        assert!(Quality::from_ascii(b"5", 64).is_err());
+    }
+
+    #[test]
+    fn encodes_qualities_within_printable_characters() {
+        let quality = Quality::from_ascii(b"!I~", 33).unwrap();
+        assert_eq!(quality.to_ascii(33), b"!I~");
+        assert_eq!(quality.to_string(), "!I~");
+        // 93 does not fit above an offset of 64
+        assert_eq!(quality.to_ascii(64), b"@h~");
+        assert_eq!(quality.reversed().scores(), [93, 40, 0]);
+    }
+
+    #[test]
+    fn parses_sequences() {
+        assert_eq!("ACGTn=.".parse::<Sequence>().unwrap().as_str(), "ACGTn=.");
+        let missing: Sequence = "*".parse().unwrap();
+        assert!(missing.is_empty());
+        assert_eq!(missing.to_string(), "*");
+        assert!("AC GT".parse::<Sequence>().is_err());
+        assert!("AC*".parse::<Sequence>().is_err());
+    }
+
+    #[test]
+    fn packs_two_bases_per_byte() {
+        let sequence: Sequence = "=ACGTN".parse().unwrap();
+        assert_eq!(sequence.to_packed(), [0x01, 0x24, 0x8f]);
+        assert_eq!(Sequence::from_packed(&sequence.to_packed(), 6), sequence);
+    }
+
+    #[test]
+    fn packs_odd_lengths_and_bases_without_a_code() {
+        let sequence: Sequence = "acgRy.".parse().unwrap();
+        let packed = sequence.to_packed();
+        assert_eq!(Sequence::from_packed(&packed, 6).as_str(), "ACGRYN");
+        let sequence: Sequence = "ACG".parse().unwrap();
+        assert_eq!(sequence.to_packed(), [0x12, 0x40]);
+        assert_eq!(Sequence::from_packed(&sequence.to_packed(), 3), sequence);
+        assert!(Sequence::default().to_packed().is_empty());
+    }
+
+    #[test]
+    fn reverse_complements_sequences() {
+        let sequence: Sequence = "AACGTNr".parse().unwrap();
+        assert_eq!(sequence.reverse_complement().as_str(), "yNACGTT");
+        assert!(Sequence::default().reverse_complement().is_empty());
+    }
+
+    #[test]
+    fn averages_scores() {
+        assert_eq!(Quality::new(vec![10, 20, 40]).mean(), Some(70.0 / 3.0));
+        assert_eq!(Quality::new(Vec::new()).mean(), None);
+        assert!(Quality::new(Vec::new()).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use std::{fmt, str::FromStr};
+
+use crate::header::parser::ParseError;
+
+/// An optional field of an alignment, written `TG:TYPE:VALUE`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Tag {
+    pub(crate) name: [u8; 2],
+    pub(crate) value: TagValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TagValue {
+    // A
+    Char(u8),
+    // i
+    Int(i64),
+    // f
+    Float(f32),
+    // Z
+    String(String),
+    // H
+    Hex(String),
+    // B, kept as the element type followed by the comma-separated values
+    Array(u8, String),
+}
+
+impl TagValue {
+    /// The SAM type of the value: `A`, `i`, `f`, `Z`, `H` or `B`.
+    pub(crate) const fn type_code(&self) -> u8 {
+        match self {
+            Self::Char(_) => b'A',
+            Self::Int(_) => b'i',
+            Self::Float(_) => b'f',
+            Self::String(_) => b'Z',
+            Self::Hex(_) => b'H',
+            Self::Array(..) => b'B',
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) | Self::Hex(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Tag {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        if bytes.len() < 5 || bytes[2] != b':' || bytes[4] != b':' {
+            return Err(ParseError::BadTag);
+        }
+        let name = [bytes[0], bytes[1]];
+        if !name[0].is_ascii_alphabetic() || !name[1].is_ascii_alphanumeric() {
+            return Err(ParseError::BadTag);
+        }
+
+        let value = &s[5..];
+        let value = match bytes[3] {
+            b'A' => match value.as_bytes() {
+                &[c] if c.is_ascii_graphic() => TagValue::Char(c),
+                _ => return Err(ParseError::BadTag),
+            },
+            b'i' => TagValue::Int(value.parse().map_err(|_| ParseError::BadTag)?),
+            b'f' => TagValue::Float(value.parse().map_err(|_| ParseError::BadTag)?),
+            b'Z' => TagValue::String(value.into()),
+            b'H' => {
+                if !value.len().is_multiple_of(2) || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(ParseError::BadTag);
+                }
+                TagValue::Hex(value.into())
+            }
+            b'B' => {
+                let (subtype, values) = value.split_once(',').unwrap_or((value, ""));
+                match subtype.as_bytes() {
+                    &[t @ (b'c' | b'C' | b's' | b'S' | b'i' | b'I' | b'f')] => {
+                        TagValue::Array(t, values.into())
+                    }
+                    _ => return Err(ParseError::BadTag),
+                }
+            }
+            _ => return Err(ParseError::BadTag),
+        };
+        Ok(Self { name, value })
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b] = self.name;
+        let code = self.value.type_code();
+        write!(
+            f,
+            "{}{}:{}:",
+            char::from(a),
+            char::from(b),
+            char::from(code)
+        )?;
+        match &self.value {
+            TagValue::Array(t, values) if values.is_empty() => write!(f, "{}", char::from(*t)),
+            TagValue::Array(t, values) => write!(f, "{},{values}", char::from(*t)),
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Writes the value alone, the comma-separated elements for arrays.
+impl fmt::Display for TagValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Char(c) => write!(f, "{}", char::from(*c)),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Float(x) => write!(f, "{x}"),
+            Self::String(s) | Self::Hex(s) => f.write_str(s),
+            Self::Array(_, values) => f.write_str(values),
+        }
+    }
+}
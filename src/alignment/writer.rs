@@ -0,0 +1,29 @@
+use std::io::{self, Write};
+
+use crate::alignment::{Alignment, position::Position};
+
+/// Writes the alignment as a SAM text line, including its optional fields.
+pub(crate) fn write_alignment(w: &mut impl Write, alignment: &Alignment) -> io::Result<()> {
+    write!(
+        w,
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        alignment.query_name,
+        alignment.flag.bits(),
+        alignment.ref_seq_name,
+        alignment.pos.map_or(0, Position::get),
+        alignment.map_quality,
+        alignment.cigar,
+        alignment.rnext,
+        alignment.pnext.map_or(0, Position::get),
+        alignment.template_len,
+        alignment.sequence,
+        match &alignment.quality {
+            Some(quality) => quality.to_string(),
+            None => "*".into(),
+        },
+    )?;
+    for tag in &alignment.tags {
+        write!(w, "\t{tag}")?;
+    }
+    writeln!(w)
+}
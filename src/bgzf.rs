@@ -0,0 +1,237 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use flate2::read::DeflateDecoder;
+
+use crate::header::parser::ParseError;
+
+const HEADER_LEN: usize = 18;
+
+/// The most data a block may hold once decompressed.
+const MAX_BLOCK_LEN: u32 = 65536;
+
+/// The empty block ending every BGZF file; without it the file was likely truncated.
+pub(crate) const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// `true` if `bytes` start with the gzip magic number, which BGZF shares.
+pub(crate) fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
+
+/// `true` if `bytes` start with a BGZF block header: gzip magic, deflate, FEXTRA set, with a
+/// single 6 byte "BC" extra subfield.
+pub(crate) fn is_bgzf(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_LEN
+        && is_gzip(bytes)
+        && bytes[2] == 8
+        && bytes[3] & 4 != 0
+        && bytes[10..14] == [6, 0, b'B', b'C']
+}
+
+/// Reads a single BGZF block, returning its compressed size and its decompressed data.
+///
+/// Returns `None` at the end of the input.
+pub(crate) fn read_block(r: &mut impl Read) -> Result<Option<(u64, Vec<u8>)>, ParseError> {
+    let mut header = [0; HEADER_LEN];
+    match r.read_exact(&mut header[..1]) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(_) => return Err(ParseError::IOError),
+    }
+    r.read_exact(&mut header[1..])
+        .map_err(|_| ParseError::BadBgzf)?;
+
+    if !is_bgzf(&header) {
+        return Err(ParseError::BadBgzf);
+    }
+    let block_size = usize::from(u16::from_le_bytes([header[16], header[17]])) + 1;
+    if block_size < HEADER_LEN + 8 {
+        return Err(ParseError::BadBgzf);
+    }
+
+    let mut rest = vec![0; block_size - HEADER_LEN];
+    r.read_exact(&mut rest).map_err(|_| ParseError::BadBgzf)?;
+    let (cdata, footer) = rest.split_at(rest.len() - 8);
+    let isize = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+    if isize > MAX_BLOCK_LEN {
+        return Err(ParseError::BadBgzf);
+    }
+
+    // One byte more than announced is enough to tell the size is wrong
+    let mut data = Vec::with_capacity(isize as usize);
+    DeflateDecoder::new(cdata)
+        .take(u64::from(isize) + 1)
+        .read_to_end(&mut data)
+        .map_err(|_| ParseError::BadBgzf)?;
+    if data.len() != isize as usize {
+        return Err(ParseError::BadBgzf);
+    }
+    Ok(Some((block_size as u64, data)))
+}
+
+/// Start offsets of each block after the first, as (compressed, uncompressed) pairs.
+///
+/// This is the content of a `.gzi` index.
+pub(crate) type BlockIndex = Vec<(u64, u64)>;
+
+pub(crate) fn index_blocks(r: &mut impl Read) -> Result<BlockIndex, ParseError> {
+    let mut index = Vec::new();
+    let (mut compressed, mut uncompressed) = (0, 0);
+    while let Some((size, data)) = read_block(r)? {
+        if compressed > 0 && !data.is_empty() {
+            index.push((compressed, uncompressed));
+        }
+        compressed += size;
+        uncompressed += data.len() as u64;
+    }
+    Ok(index)
+}
+
+pub(crate) fn read_gzi(r: &mut impl Read) -> Result<BlockIndex, ParseError> {
+    let mut read_u64 = || {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf).map_err(|_| ParseError::BadIndex)?;
+        Ok(u64::from_le_bytes(buf))
+    };
+    let n = read_u64()?;
+    (0..n).map(|_| Ok((read_u64()?, read_u64()?))).collect()
+}
+
+pub(crate) fn write_gzi(w: &mut impl Write, index: &BlockIndex) -> io::Result<()> {
+    w.write_all(&(index.len() as u64).to_le_bytes())?;
+    for (compressed, uncompressed) in index {
+        w.write_all(&compressed.to_le_bytes())?;
+        w.write_all(&uncompressed.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Decompresses a BGZF stream, with random access through its block index.
+pub(crate) struct Reader<R> {
+    inner: R,
+    index: BlockIndex,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    pub(crate) fn new(inner: R, index: BlockIndex) -> Self {
+        Self {
+            inner,
+            index,
+            block: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Moves to the given offset of the decompressed data.
+    pub(crate) fn seek_uncompressed(&mut self, offset: u64) -> Result<(), ParseError> {
+        let i = self.index.partition_point(|&(_, u)| u <= offset);
+        let (compressed, uncompressed) = i.checked_sub(1).map_or((0, 0), |i| self.index[i]);
+
+        self.inner
+            .seek(SeekFrom::Start(compressed))
+            .map_err(|_| ParseError::IOError)?;
+        self.block.clear();
+        self.pos = 0;
+
+        let mut skip = offset - uncompressed;
+        loop {
+            let Some((_, data)) = read_block(&mut self.inner)? else {
+                return Ok(());
+            };
+            if skip < data.len() as u64 {
+                self.block = data;
+                self.pos = skip as usize;
+                return Ok(());
+            }
+            skip -= data.len() as u64;
+        }
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.block.len() {
+            match read_block(&mut self.inner) {
+                Ok(Some((_, data))) => {
+                    self.block = data;
+                    self.pos = 0;
+                }
+                Ok(None) => return Ok(0),
+                Err(_) => return Err(io::ErrorKind::InvalidData.into()),
+            }
+        }
+        let n = buf.len().min(self.block.len() - self.pos);
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compression, write::DeflateEncoder};
+
+    use super::*;
+
+    /// A BGZF block of `data`, announcing `isize` decompressed bytes.
+    fn block(data: &[u8], isize: u32) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let cdata = encoder.finish().unwrap();
+        let block_size = (HEADER_LEN + cdata.len() + 8 - 1) as u16;
+
+        let mut block = EOF_BLOCK[..16].to_vec();
+        block.extend(block_size.to_le_bytes());
+        block.extend(cdata);
+        // The CRC32 is not checked
+        block.extend([0; 4]);
+        block.extend(isize.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn reads_blocks_until_the_end() {
+        let mut bytes = block(b"ACGT", 4);
+        let first_len = bytes.len() as u64;
+        bytes.extend(EOF_BLOCK);
+        let mut r = &bytes[..];
+
+        let (size, data) = read_block(&mut r).unwrap().unwrap();
+        assert_eq!((size, data.as_slice()), (first_len, &b"ACGT"[..]));
+        let (size, data) = read_block(&mut r).unwrap().unwrap();
+        assert_eq!((size, data.len()), (EOF_BLOCK.len() as u64, 0));
+        assert!(read_block(&mut r).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_blocks() {
+        let bytes = block(b"ACGT", 4);
+        for len in [1, HEADER_LEN - 1, HEADER_LEN, bytes.len() - 1] {
+            let result = read_block(&mut &bytes[..len]);
+            assert!(matches!(result, Err(ParseError::BadBgzf)), "{len}");
+        }
+    }
+
+    #[test]
+    fn rejects_plain_gzip() {
+        let mut bytes = block(b"ACGT", 4);
+        bytes[12..14].copy_from_slice(b"XY");
+        assert!(matches!(
+            read_block(&mut &bytes[..]),
+            Err(ParseError::BadBgzf)
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_decompressed_sizes() {
+        for isize in [3, 5, MAX_BLOCK_LEN + 1, u32::MAX] {
+            let bytes = block(b"ACGT", isize);
+            let result = read_block(&mut &bytes[..]);
+            assert!(matches!(result, Err(ParseError::BadBgzf)), "{isize}");
+        }
+    }
+}
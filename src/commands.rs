@@ -0,0 +1,170 @@
+pub mod addreplacerg;
+pub mod collate;
+pub mod coverage;
+pub mod depth;
+pub mod dict;
+pub mod dictdiff;
+pub mod faidx;
+pub mod fastq;
+pub mod fixmate;
+pub mod flags;
+pub mod import;
+pub mod markdup;
+pub mod mpileup;
+pub mod provenance;
+pub mod quickcheck;
+pub mod reheader;
+pub mod renamecontigs;
+pub mod split;
+pub mod validate;
+
+use std::{
+    env, fmt, fs,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    str::FromStr,
+};
+
+use crate::{
+    header::{Header, Program, parser::ParseError},
+    sam::{reader::Reader, validation::Stringency},
+};
+
+#[derive(Debug)]
+pub enum CommandError {
+    UnknownCommand(String),
+    Usage(String),
+    Parse(ParseError),
+    IOError(io::ErrorKind),
+    UnknownReference(String),
+    UnsortedInput,
+    MismatchedMates(String),
+    UnmappedContig(String),
+    Invalid(u64),
+    // Output path and the two keys or outputs writing to it
+    SharedOutput(String, String, String),
+    // Name of a file format the command cannot read
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(c) => write!(f, "unknown command: {c}"),
+            Self::Usage(u) => write!(f, "usage: {u}"),
+            Self::Parse(e) => write!(f, "parse error: {e}"),
+            Self::IOError(e) => write!(f, "I/O error: {e}"),
+            Self::UnknownReference(r) => write!(f, "reference not in header: {r}"),
+            Self::UnsortedInput => write!(f, "input is not coordinate-sorted"),
+            Self::MismatchedMates(name) => write!(f, "mate of {name} missing or out of order"),
+            Self::UnmappedContig(name) => write!(f, "no new name for contig {name}"),
+            Self::Invalid(count) => write!(f, "{count} problems found"),
+            Self::SharedOutput(path, a, b) => {
+                write!(f, "{a} and {b} would both be written to {path}")
+            }
+            Self::UnsupportedFormat(format) => write!(f, "{format} input is not supported"),
+        }
+    }
+}
+
+impl From<ParseError> for CommandError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<io::Error> for CommandError {
+    fn from(e: io::Error) -> Self {
+        Self::IOError(e.kind())
+    }
+}
+
+/// Runs `command`, checking the records it reads from SAM with `stringency`.
+pub fn run(
+    command: &str,
+    args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    match command {
+        "addreplacerg" => addreplacerg::run(args, stringency),
+        "collate" => collate::run(args, stringency),
+        "coverage" => coverage::run(args, stringency),
+        "depth" => depth::run(args, stringency),
+        "dict" => dict::run(args),
+        "dictdiff" => dictdiff::run(args),
+        "faidx" => faidx::run(args),
+        "fasta" => fastq::run(args, fastq::Format::Fasta, stringency),
+        "fastq" => fastq::run(args, fastq::Format::Fastq, stringency),
+        "fixmate" => fixmate::run(args, stringency),
+        "flags" => flags::run(args),
+        "import" => import::run(args),
+        "markdup" => markdup::run(args, stringency),
+        "mpileup" => mpileup::run(args, stringency),
+        "provenance" => provenance::run(args),
+        "quickcheck" => quickcheck::run(args),
+        "reheader" => reheader::run(args),
+        "renamecontigs" => renamecontigs::run(args, stringency),
+        "split" => split::run(args, stringency),
+        "validate" => validate::run(args),
+        _ => Err(CommandError::UnknownCommand(command.into())),
+    }
+}
+
+/// Takes the value of the option `opt` from the remaining arguments.
+fn value<T: FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    opt: &str,
+) -> Result<T, CommandError> {
+    args.next()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| CommandError::Usage(format!("bad or missing value for {opt}")))
+}
+
+/// Takes a flag value given in decimal or as `0x`-prefixed hexadecimal.
+fn flags_value(args: &mut impl Iterator<Item = String>, opt: &str) -> Result<u16, CommandError> {
+    let v: String = value(args, opt)?;
+    match v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => v.parse().ok(),
+    }
+    .ok_or_else(|| CommandError::Usage(format!("bad flags for {opt}: {v}")))
+}
+
+fn unknown_option(opt: &str) -> CommandError {
+    CommandError::Usage(format!("unknown option {opt}"))
+}
+
+fn is_option(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with('-')
+}
+
+/// Records this run in the header of an output as a @PG line, as done by every command writing
+/// SAM unless given `--no-PG`.
+fn add_program_record(header: &mut Header) {
+    let command_line = env::args().collect::<Vec<_>>().join(" ");
+    header.append_program(
+        Program::new("samovar".into())
+            .with_name(Some("samovar".into()))
+            .with_version(Some(env!("CARGO_PKG_VERSION").into()))
+            .with_command_line(Some(command_line)),
+    );
+}
+
+/// Opens a SAM file for reading, with `-` meaning stdin, checking records with `stringency`.
+fn open_sam(path: &str, stringency: Stringency) -> Result<Reader<Box<dyn BufRead>>, CommandError> {
+    let reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(fs::File::open(path)?))
+    };
+    let mut reader = Reader::new(reader)?;
+    reader.set_stringency(stringency)?;
+    Ok(reader)
+}
+
+/// Opens an output file, with no path or `-` meaning stdout.
+fn create_output(path: Option<&str>) -> Result<Box<dyn Write>, CommandError> {
+    Ok(match path {
+        None | Some("-") => Box::new(BufWriter::new(io::stdout().lock())),
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+    })
+}
@@ -0,0 +1,139 @@
+use std::io::Write;
+
+use crate::{
+    alignment::{Alignment, tag::TagValue, writer::write_alignment},
+    commands::{
+        CommandError, add_program_record, create_output, is_option, open_sam, unknown_option, value,
+    },
+    header::{Header, ReadGroup, writer::write_header},
+    sam::validation::Stringency,
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    /// Every record gets the read group
+    OverwriteAll,
+    /// Only records without an RG tag get the read group
+    OrphanOnly,
+    /// Records get the read group whose ID or barcode is the value of the tag
+    Tag([u8; 2]),
+}
+
+/// Parses a read group given on the command line, as `ID:x\tSM:y` with or without a leading
+/// `@RG`, and with tabs possibly written as a literal `\t`.
+fn parse_read_group_arg(arg: &str) -> Result<ReadGroup, CommandError> {
+    let fields = arg.replace("\\t", "\t");
+    let fields = fields.strip_prefix("@RG\t").unwrap_or(&fields);
+    Ok(fields.parse()?)
+}
+
+/// The read group a record gets, `None` to leave its RG tag as it is.
+fn assign<'a>(
+    alignment: &Alignment,
+    mode: Mode,
+    default: Option<&'a str>,
+    header: &'a Header,
+) -> Option<&'a str> {
+    match mode {
+        Mode::OverwriteAll => default,
+        Mode::OrphanOnly if alignment.tag(b"RG").is_none() => default,
+        Mode::OrphanOnly => None,
+        Mode::Tag(name) => {
+            let value = alignment.tag(&name)?.value.to_string();
+            header
+                .read_groups()
+                .find(|rg| rg.id() == value || rg.barcode() == Some(&value))
+                .map(ReadGroup::id)
+        }
+    }
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut read_groups = Vec::new();
+    let mut default_id = None;
+    let mut mode = Mode::OverwriteAll;
+    let mut tag = None;
+    let mut replace_header = false;
+    let mut output = None;
+    let mut no_pg = false;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" => read_groups.push(parse_read_group_arg(&value::<String>(&mut args, "-r")?)?),
+            "-R" => default_id = Some(value::<String>(&mut args, "-R")?),
+            "-m" => {
+                mode = match value::<String>(&mut args, "-m")?.as_str() {
+                    "overwrite_all" => Mode::OverwriteAll,
+                    "orphan_only" => Mode::OrphanOnly,
+                    "tag" => Mode::Tag(*b"BC"),
+                    other => return Err(CommandError::Usage(format!("unknown mode {other}"))),
+                }
+            }
+            "-t" => {
+                let name: String = value(&mut args, "-t")?;
+                tag = match name.as_bytes() {
+                    &[a, b] => Some([a, b]),
+                    _ => return Err(CommandError::Usage(format!("bad tag {name}"))),
+                };
+            }
+            "-w" => replace_header = true,
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar addreplacerg [-r '@RG\\tID:..'...] [-R id] [-m overwrite_all|orphan_only|tag] [-t tag] [-w] [--no-PG] [-o out] <in.sam>".into(),
+        ));
+    };
+    match (mode, tag) {
+        (Mode::Tag(_), Some(tag)) => mode = Mode::Tag(tag),
+        (_, Some(_)) => return Err(CommandError::Usage("-t needs -m tag".into())),
+        (_, None) => {}
+    }
+
+    let reader = open_sam(&path, stringency)?;
+    let mut header = reader.header().clone();
+    // The first new read group is the one given to records, unless -R picks another
+    let default_id = default_id.or_else(|| read_groups.first().map(|rg| rg.id().to_owned()));
+    for read_group in read_groups {
+        if replace_header {
+            header.set_read_group(read_group);
+        } else {
+            header.add_read_group(read_group)?;
+        }
+    }
+    if let Some(id) = &default_id
+        && header.read_group(id).is_none()
+    {
+        return Err(CommandError::Usage(format!("no @RG line with ID {id}")));
+    }
+    if default_id.is_none() && !matches!(mode, Mode::Tag(_)) {
+        return Err(CommandError::Usage(
+            "no read group given with -r or -R".into(),
+        ));
+    }
+
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+    for alignment in reader {
+        let mut alignment = alignment?;
+        if let Some(id) = assign(&alignment, mode, default_id.as_deref(), &header) {
+            alignment.set_tag(*b"RG", TagValue::String(id.to_owned()));
+        }
+        write_alignment(&mut out, &alignment)?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    alignment::{Alignment, reader::Alignments, writer::write_alignment},
+    commands::{
+        CommandError, add_program_record, create_output, is_option, open_sam, unknown_option, value,
+    },
+    header::{AlignmentGrouping, SortOrder, writer::write_header},
+    sam::validation::Stringency,
+};
+
+/// Temporary SAM files holding the alignments, split by a hash of their query name.
+///
+/// Each bucket holds whole name groups, so it can be collated on its own in memory.
+struct Buckets {
+    paths: Vec<PathBuf>,
+    writers: Vec<BufWriter<fs::File>>,
+}
+
+impl Buckets {
+    fn create(dir: &Path, count: usize) -> Result<Self, CommandError> {
+        let mut buckets = Self {
+            paths: Vec::new(),
+            writers: Vec::new(),
+        };
+        for i in 0..count {
+            let path = dir.join(format!("samovar-collate.{}.{i}.sam", process::id()));
+            // Pushed first so the file is removed even if creating it fails halfway
+            buckets.paths.push(path.clone());
+            buckets
+                .writers
+                .push(BufWriter::new(fs::File::create(path)?));
+        }
+        Ok(buckets)
+    }
+
+    fn add(&mut self, alignment: &Alignment) -> Result<(), CommandError> {
+        let mut hasher = DefaultHasher::new();
+        alignment.query_name().hash(&mut hasher);
+        let i = hasher.finish() as usize % self.writers.len();
+        write_alignment(&mut self.writers[i], alignment)?;
+        Ok(())
+    }
+
+    /// Writes the content of each bucket with alignments of the same name next to each other.
+    fn collate_into(mut self, out: &mut impl Write) -> Result<(), CommandError> {
+        for writer in &mut self.writers {
+            writer.flush()?;
+        }
+        for path in &self.paths {
+            let mut groups: IndexMap<String, Vec<Alignment>> = IndexMap::new();
+            for alignment in Alignments::new(BufReader::new(fs::File::open(path)?)) {
+                let alignment = alignment?;
+                groups
+                    .entry(alignment.query_name().to_owned())
+                    .or_default()
+                    .push(alignment);
+            }
+            for alignment in groups.values().flatten() {
+                write_alignment(out, alignment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Buckets {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Writes primary mates as soon as both have been seen, spilling to `buckets` when more than
+/// `max_pending` reads are waiting for their mate.
+///
+/// Secondary and supplementary alignments are dropped.
+fn collate_fast(
+    alignments: impl Iterator<Item = Result<Alignment, CommandError>>,
+    out: &mut impl Write,
+    buckets: &mut Buckets,
+    max_pending: usize,
+) -> Result<(), CommandError> {
+    let mut pending: HashMap<String, Alignment> = HashMap::new();
+    for alignment in alignments {
+        let alignment = alignment?;
+        let flag = alignment.flag();
+        if !flag.is_primary_line() {
+            continue;
+        }
+        if !flag.has_multiple_segments() {
+            write_alignment(out, &alignment)?;
+            continue;
+        }
+        match pending.remove(alignment.query_name()) {
+            Some(mate) => {
+                let (first, second) = if alignment.flag().is_first_segment() {
+                    (alignment, mate)
+                } else {
+                    (mate, alignment)
+                };
+                write_alignment(out, &first)?;
+                write_alignment(out, &second)?;
+            }
+            None => {
+                if pending.len() >= max_pending {
+                    for (_, spilled) in pending.drain() {
+                        buckets.add(&spilled)?;
+                    }
+                }
+                pending.insert(alignment.query_name().to_owned(), alignment);
+            }
+        }
+    }
+    for (_, alignment) in pending {
+        buckets.add(&alignment)?;
+    }
+    Ok(())
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut bucket_count = 64;
+    let mut fast = false;
+    let mut max_pending = 10000;
+    let mut tmp_dir = env::temp_dir();
+    let mut output = None;
+    let mut no_pg = false;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => bucket_count = value(&mut args, "-n")?,
+            "-f" => fast = true,
+            "-r" => max_pending = value(&mut args, "-r")?,
+            "-T" => tmp_dir = value::<String>(&mut args, "-T")?.into(),
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let (Some(path), 1..) = (path, bucket_count) else {
+        return Err(CommandError::Usage(
+            "samovar collate [-f] [-r maxPending] [-n buckets] [-T tmpDir] [--no-PG] [-o out] <in.sam>"
+                .into(),
+        ));
+    };
+
+    let reader = open_sam(&path, stringency)?;
+    let mut header = reader.header().clone();
+    header.set_sorting(SortOrder::Unsorted, Some(AlignmentGrouping::Query));
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+
+    let mut buckets = Buckets::create(&tmp_dir, bucket_count)?;
+    let alignments = reader.map(|a| a.map_err(CommandError::from));
+    if fast {
+        collate_fast(alignments, &mut out, &mut buckets, max_pending)?;
+    } else {
+        for alignment in alignments {
+            buckets.add(&alignment?)?;
+        }
+    }
+    buckets.collate_into(&mut out)?;
+    out.flush()?;
+    Ok(())
+}
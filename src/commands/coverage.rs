@@ -0,0 +1,293 @@
+use std::io::Write;
+
+use crate::{
+    commands::{
+        CommandError, create_output,
+        depth::{DepthSink, walk_depth},
+        flags_value, is_option, open_sam, unknown_option, value,
+    },
+    depth::{Counted, DepthOptions},
+    header::Header,
+    sam::{merge::Merged, validation::Stringency},
+};
+
+const HISTOGRAM_ROWS: usize = 10;
+
+#[derive(Debug, Default)]
+struct ReferenceCoverage {
+    reads: u64,
+    covered_bases: u64,
+    depth_sum: u64,
+    bases: u64,
+    quality_sum: u64,
+    map_quality_sum: u64,
+    // Reads with an available MAPQ
+    map_quality_reads: u64,
+    // Covered bases per histogram bin
+    bins: Vec<u64>,
+}
+
+struct CoverageWriter<W> {
+    out: W,
+    // Number of histogram columns, or `None` to write the table
+    histogram: Option<usize>,
+    len: u64,
+    stats: ReferenceCoverage,
+}
+
+impl<W: Write> CoverageWriter<W> {
+    fn write_table_row(&mut self, name: &str) -> Result<(), CommandError> {
+        let s = &self.stats;
+        writeln!(
+            self.out,
+            "{name}\t1\t{}\t{}\t{}\t{:.4}\t{:.4}\t{:.1}\t{:.1}",
+            self.len,
+            s.reads,
+            s.covered_bases,
+            ratio(s.covered_bases, self.len) * 100.0,
+            ratio(s.depth_sum, self.len),
+            ratio(s.quality_sum, s.bases),
+            ratio(s.map_quality_sum, s.map_quality_reads),
+        )?;
+        Ok(())
+    }
+
+    fn write_histogram(&mut self, name: &str) -> Result<(), CommandError> {
+        let s = &self.stats;
+        let columns = s.bins.len() as u64;
+        let bin_width = self.len.div_ceil(columns).max(1);
+        let percents: Vec<f64> = s
+            .bins
+            .iter()
+            .enumerate()
+            .map(|(i, &covered)| {
+                let start = i as u64 * bin_width;
+                let width = self.len.saturating_sub(start).min(bin_width);
+                ratio(covered, width) * 100.0
+            })
+            .collect();
+        let max = percents.iter().copied().fold(0.0, f64::max);
+
+        let labels = [
+            format!("Number of reads: {}", s.reads),
+            String::new(),
+            format!("Covered bases:   {}", format_bp(s.covered_bases)),
+            format!(
+                "Percent covered: {:.4}%",
+                ratio(s.covered_bases, self.len) * 100.0
+            ),
+            format!("Mean coverage:   {:.3}x", ratio(s.depth_sum, self.len)),
+            format!("Mean baseQ:      {:.1}", ratio(s.quality_sum, s.bases)),
+            format!(
+                "Mean mapQ:       {:.1}",
+                ratio(s.map_quality_sum, s.map_quality_reads)
+            ),
+            String::new(),
+            format!("Histo bin width: {}", format_bp(bin_width)),
+            format!("Histo max bin:   {max:.3}%"),
+        ];
+
+        writeln!(self.out, "{name} ({})", format_bp(self.len))?;
+        for (row, label) in labels.iter().enumerate() {
+            let threshold = max * (HISTOGRAM_ROWS - row) as f64 / HISTOGRAM_ROWS as f64;
+            let bars: String = percents
+                .iter()
+                .map(|&p| {
+                    if p > 0.0 && p >= threshold {
+                        '█'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect();
+            let line = format!(">{threshold:7.2}% │{bars}│ {label}");
+            writeln!(self.out, "{}", line.trim_end())?;
+        }
+        writeln!(
+            self.out,
+            "{:>10}1{:>width$}",
+            "",
+            self.len,
+            width = s.bins.len() + 1
+        )?;
+        Ok(())
+    }
+}
+
+impl<W: Write> DepthSink for CoverageWriter<W> {
+    fn start_reference(&mut self, header: &Header, ref_index: usize) -> Result<(), CommandError> {
+        self.len = header.reference_seq_at(ref_index).unwrap().length();
+        let columns = self.histogram.unwrap_or(0);
+        self.stats = ReferenceCoverage {
+            bins: vec![0; columns],
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    fn alignment(&mut self, map_quality: Option<u8>, counted: Counted) {
+        self.stats.reads += 1;
+        if let Some(map_quality) = map_quality {
+            self.stats.map_quality_reads += 1;
+            self.stats.map_quality_sum += u64::from(map_quality);
+        }
+        self.stats.bases += counted.bases;
+        self.stats.quality_sum += counted.quality_sum;
+    }
+
+    fn position(&mut self, pos: u32, depths: &[u32]) -> Result<(), CommandError> {
+        let depth: u64 = depths.iter().map(|&d| u64::from(d)).sum();
+        self.stats.covered_bases += 1;
+        self.stats.depth_sum += depth;
+        if !self.stats.bins.is_empty() {
+            let bin_width = self.len.div_ceil(self.stats.bins.len() as u64).max(1);
+            let bin = ((u64::from(pos) - 1) / bin_width) as usize;
+            if let Some(covered) = self.stats.bins.get_mut(bin) {
+                *covered += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn end_reference(&mut self, header: &Header, ref_index: usize) -> Result<(), CommandError> {
+        let name = header
+            .reference_seq_at(ref_index)
+            .unwrap()
+            .name()
+            .to_owned();
+        match self.histogram {
+            Some(_) => self.write_histogram(&name),
+            None => self.write_table_row(&name),
+        }
+    }
+}
+
+fn ratio(n: u64, d: u64) -> f64 {
+    if d == 0 { 0.0 } else { n as f64 / d as f64 }
+}
+
+fn format_bp(n: u64) -> String {
+    match n {
+        0..1_000 => format!("{n}bp"),
+        1_000..1_000_000 => format!("{:.2}Kbp", n as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.2}Mbp", n as f64 / 1e6),
+        _ => format!("{:.2}Gbp", n as f64 / 1e9),
+    }
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut options = DepthOptions::default();
+    let mut histogram = false;
+    let mut columns = 50;
+    let mut print_header = true;
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-m" => histogram = true,
+            "-w" => columns = value(&mut args, "-w")?,
+            "-H" => print_header = false,
+            "-J" => options.count_deletions = true,
+            "-s" => options.remove_overlaps = true,
+            "-q" => options.min_base_quality = value(&mut args, "-q")?,
+            "-Q" => options.min_map_quality = value(&mut args, "-Q")?,
+            "-l" => options.min_read_len = value(&mut args, "-l")?,
+            "--rf" => options.include_flags = flags_value(&mut args, "--rf")?,
+            "--ff" => options.exclude_flags = flags_value(&mut args, "--ff")?,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || columns == 0 {
+        return Err(CommandError::Usage(
+            "samovar coverage [-m [-w cols]] [-H] [-J] [-s] [-q minBQ] [-Q minMQ] [-l minLen] [--rf flags] [--ff flags] [-o out] <in.sam>...".into(),
+        ));
+    }
+
+    let readers = paths
+        .iter()
+        .map(|p| open_sam(p, stringency))
+        .collect::<Result<_, _>>()?;
+    let mut out = create_output(output.as_deref())?;
+    if print_header && !histogram {
+        writeln!(
+            out,
+            "#rname\tstartpos\tendpos\tnumreads\tcovbases\tcoverage\tmeandepth\tmeanbaseq\tmeanmapq"
+        )?;
+    }
+
+    let mut writer = CoverageWriter {
+        out,
+        histogram: histogram.then_some(columns),
+        len: 0,
+        stats: ReferenceCoverage::default(),
+    };
+    walk_depth(Merged::new(readers), &options, &mut writer)?;
+    writer.out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sam::reader::Reader;
+
+    // Reads covering 1-4, 3-4 and 7-8 around a deletion, and 10 with an unavailable MAPQ
+    const SAM: &str = "@SQ\tSN:chr1\tLN:10
+r1\t0\tchr1\t1\t60\t4M\t*\t0\t0\tACGT\tIIII
+r2\t0\tchr1\t3\t30\t2M2D2M\t*\t0\t0\tACGT\tIIII
+r3\t0\tchr1\t10\t255\t1M\t*\t0\t0\tA\tI
+";
+
+    fn coverage(options: &DepthOptions, histogram: Option<usize>) -> CoverageWriter<Vec<u8>> {
+        let mut writer = CoverageWriter {
+            out: Vec::new(),
+            histogram,
+            len: 0,
+            stats: ReferenceCoverage::default(),
+        };
+        let reader = Reader::new(SAM.as_bytes()).unwrap();
+        walk_depth(Merged::new(vec![reader]), options, &mut writer).unwrap();
+        writer
+    }
+
+    #[test]
+    fn summarises_coverage() {
+        let writer = coverage(&DepthOptions::default(), None);
+        assert_eq!(
+            String::from_utf8(writer.out).unwrap(),
+            "chr1\t1\t10\t3\t7\t70.0000\t0.9000\t40.0\t45.0\n"
+        );
+
+        let options = DepthOptions {
+            count_deletions: true,
+            ..DepthOptions::default()
+        };
+        let writer = coverage(&options, None);
+        assert_eq!(
+            String::from_utf8(writer.out).unwrap(),
+            "chr1\t1\t10\t3\t9\t90.0000\t1.1000\t40.0\t45.0\n"
+        );
+    }
+
+    #[test]
+    fn bins_covered_bases() {
+        let writer = coverage(&DepthOptions::default(), Some(5));
+        assert_eq!(writer.stats.bins, [2, 2, 0, 2, 1]);
+        let out = String::from_utf8(writer.out).unwrap();
+        assert!(out.starts_with("chr1 (10bp)\n"));
+        assert!(out.contains("Histo bin width: 2bp\n"));
+        assert!(out.contains("Histo max bin:   100.000%\n"));
+
+        // Bins are as wide as needed to fit the reference, the last one possibly narrower
+        let writer = coverage(&DepthOptions::default(), Some(4));
+        assert_eq!(writer.stats.bins, [3, 1, 2, 1]);
+        let out = String::from_utf8(writer.out).unwrap();
+        assert!(out.contains("Histo bin width: 3bp\n"));
+    }
+}
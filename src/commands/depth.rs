@@ -0,0 +1,246 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    commands::{
+        CommandError, create_output, flags_value, is_option, open_sam, unknown_option, value,
+    },
+    depth::{Counted, DepthCounter, DepthOptions},
+    header::Header,
+    sam::{merge::Merged, validation::Stringency},
+};
+
+/// Receives the depth computed by [`walk_depth`], one reference at a time in @SQ order.
+pub(super) trait DepthSink {
+    fn start_reference(&mut self, header: &Header, ref_index: usize) -> Result<(), CommandError>;
+
+    /// Called for every alignment counted towards depth, with its MAPQ if available.
+    fn alignment(&mut self, _map_quality: Option<u8>, _counted: Counted) {}
+
+    /// Called in increasing order for positions covered by at least one counted base.
+    fn position(&mut self, pos: u32, depths: &[u32]) -> Result<(), CommandError>;
+
+    fn end_reference(&mut self, header: &Header, ref_index: usize) -> Result<(), CommandError>;
+}
+
+/// Computes depth over coordinate-sorted inputs.
+///
+/// Every reference of the first input's header is started and ended, whether or not any
+/// alignment falls on it.
+pub(super) fn walk_depth<R: BufRead>(
+    alignments: Merged<R>,
+    options: &DepthOptions,
+    sink: &mut impl DepthSink,
+) -> Result<(), CommandError> {
+    let header = alignments.header().clone();
+    let mut counter = DepthCounter::new(alignments.inputs());
+    let mut current = None;
+
+    for record in alignments {
+        let (input, alignment) = record?;
+        // Accepted alignments are placed
+        let Some(start) = alignment.position().filter(|_| options.accepts(&alignment)) else {
+            continue;
+        };
+        let ref_index = header
+            .reference_seq_index(alignment.ref_seq_name())
+            .ok_or_else(|| CommandError::UnknownReference(alignment.ref_seq_name().into()))?;
+
+        // Catch up on all references up to and including this one
+        let first = match current {
+            Some(c) if c == ref_index => None,
+            Some(c) if c > ref_index => return Err(CommandError::UnsortedInput),
+            Some(c) => {
+                finish_reference(&mut counter, sink, &header, c)?;
+                Some(c + 1)
+            }
+            None => Some(0),
+        };
+        if let Some(first) = first {
+            for skipped in first..ref_index {
+                sink.start_reference(&header, skipped)?;
+                sink.end_reference(&header, skipped)?;
+            }
+            sink.start_reference(&header, ref_index)?;
+            current = Some(ref_index);
+        }
+
+        if start.get() < counter.start() {
+            return Err(CommandError::UnsortedInput);
+        }
+        flush(&mut counter, sink, start.get())?;
+        let counted = counter.add(input, &alignment, options);
+        sink.alignment(alignment.mapping_quality(), counted);
+    }
+
+    let rest = match current {
+        Some(c) => {
+            finish_reference(&mut counter, sink, &header, c)?;
+            c + 1
+        }
+        None => 0,
+    };
+    for ref_index in rest..header.reference_seqs().count() {
+        sink.start_reference(&header, ref_index)?;
+        sink.end_reference(&header, ref_index)?;
+    }
+    Ok(())
+}
+
+fn flush(
+    counter: &mut DepthCounter,
+    sink: &mut impl DepthSink,
+    until: u32,
+) -> Result<(), CommandError> {
+    for (pos, depths) in counter.drain_until(until) {
+        if depths.iter().any(|&d| d > 0) {
+            sink.position(pos, &depths)?;
+        }
+    }
+    Ok(())
+}
+
+fn finish_reference(
+    counter: &mut DepthCounter,
+    sink: &mut impl DepthSink,
+    header: &Header,
+    ref_index: usize,
+) -> Result<(), CommandError> {
+    flush(counter, sink, u32::MAX)?;
+    counter.reset();
+    sink.end_reference(header, ref_index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ZeroDepth {
+    // Only positions with reads
+    Skip,
+    // -a: every position of references with at least one counted alignment
+    CoveredReferences,
+    // -aa: every position of every reference
+    AllReferences,
+}
+
+struct DepthWriter<W> {
+    out: W,
+    zero_depth: ZeroDepth,
+    inputs: usize,
+    ref_name: String,
+    // Next position to be written on the current reference
+    next_pos: u32,
+    any_reads: bool,
+}
+
+impl<W: Write> DepthWriter<W> {
+    fn write_row(&mut self, pos: u32, depths: &[u32]) -> Result<(), CommandError> {
+        write!(self.out, "{}\t{}", self.ref_name, pos)?;
+        for d in depths {
+            write!(self.out, "\t{d}")?;
+        }
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn write_zeros(&mut self, until: u32) -> Result<(), CommandError> {
+        let zeros = vec![0; self.inputs];
+        for pos in self.next_pos..until {
+            self.write_row(pos, &zeros)?;
+        }
+        self.next_pos = self.next_pos.max(until);
+        Ok(())
+    }
+}
+
+impl<W: Write> DepthSink for DepthWriter<W> {
+    fn start_reference(&mut self, header: &Header, ref_index: usize) -> Result<(), CommandError> {
+        self.ref_name = header.reference_seq_at(ref_index).unwrap().name().into();
+        self.next_pos = 1;
+        self.any_reads = false;
+        Ok(())
+    }
+
+    fn alignment(&mut self, _map_quality: Option<u8>, _counted: Counted) {
+        self.any_reads = true;
+    }
+
+    fn position(&mut self, pos: u32, depths: &[u32]) -> Result<(), CommandError> {
+        if self.zero_depth != ZeroDepth::Skip {
+            self.write_zeros(pos)?;
+        }
+        self.write_row(pos, depths)?;
+        self.next_pos = pos + 1;
+        Ok(())
+    }
+
+    fn end_reference(&mut self, header: &Header, ref_index: usize) -> Result<(), CommandError> {
+        let fill = match self.zero_depth {
+            ZeroDepth::Skip => false,
+            ZeroDepth::CoveredReferences => self.any_reads,
+            ZeroDepth::AllReferences => true,
+        };
+        if fill {
+            let len = header.reference_seq_at(ref_index).unwrap().length();
+            self.write_zeros(u32::try_from(len).unwrap_or(u32::MAX).saturating_add(1))?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut options = DepthOptions::default();
+    let mut zero_depth = ZeroDepth::Skip;
+    let mut print_header = false;
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // Given twice, also output references without any reads
+            "-a" if zero_depth == ZeroDepth::Skip => zero_depth = ZeroDepth::CoveredReferences,
+            "-a" | "-aa" => zero_depth = ZeroDepth::AllReferences,
+            "-H" => print_header = true,
+            "-J" => options.count_deletions = true,
+            "-s" => options.remove_overlaps = true,
+            "-q" => options.min_base_quality = value(&mut args, "-q")?,
+            "-Q" => options.min_map_quality = value(&mut args, "-Q")?,
+            "-l" => options.min_read_len = value(&mut args, "-l")?,
+            "-g" => options.include_flags = flags_value(&mut args, "-g")?,
+            "-G" => options.exclude_flags = flags_value(&mut args, "-G")?,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(CommandError::Usage(
+            "samovar depth [-a] [-H] [-J] [-s] [-q minBQ] [-Q minMQ] [-l minLen] [-g flags] [-G flags] [-o out] <in.sam>...".into(),
+        ));
+    }
+
+    let readers = paths
+        .iter()
+        .map(|p| open_sam(p, stringency))
+        .collect::<Result<_, _>>()?;
+    let mut out = create_output(output.as_deref())?;
+    if print_header {
+        write!(out, "#CHROM\tPOS")?;
+        for path in &paths {
+            write!(out, "\t{path}")?;
+        }
+        writeln!(out)?;
+    }
+
+    let mut writer = DepthWriter {
+        out,
+        zero_depth,
+        inputs: paths.len(),
+        ref_name: String::new(),
+        next_pos: 1,
+        any_reads: false,
+    };
+    walk_depth(Merged::new(readers), &options, &mut writer)?;
+    writer.out.flush()?;
+    Ok(())
+}
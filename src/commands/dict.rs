@@ -0,0 +1,118 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use flate2::bufread::MultiGzDecoder;
+
+use crate::{
+    bgzf,
+    commands::{CommandError, create_output, is_option, unknown_option, value},
+    header::{Header, HeaderMeta, ReferenceSeq, SortOrder, writer::write_header},
+};
+
+struct DictOptions {
+    assembly_id: Option<String>,
+    species: Option<String>,
+    uri: Option<String>,
+}
+
+/// Builds one @SQ record per sequence of a plain or gzip-compressed FASTA stream.
+///
+/// The M5 checksum is computed over the upper-cased bases, as the SAM specification requires.
+fn read_ref_seqs(
+    reader: impl BufRead,
+    options: &DictOptions,
+) -> Result<Vec<ReferenceSeq>, CommandError> {
+    let mut ref_seqs = Vec::new();
+    let mut current: Option<(String, u64, md5::Context)> = None;
+    let finish = |(name, length, md5): (String, u64, md5::Context)| {
+        ReferenceSeq::new(name, length)
+            .with_assembly_id(options.assembly_id.clone())
+            .with_checksum(Some(format!("{:x}", md5.finalize())))
+            .with_species(options.species.clone())
+            .with_uri(options.uri.clone())
+    };
+
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let line = line.trim_ascii_end();
+        if let Some(name) = line.strip_prefix(b">") {
+            let name = String::from_utf8_lossy(name);
+            let name = name
+                .split_whitespace()
+                .next()
+                .ok_or(CommandError::Usage("FASTA sequence without a name".into()))?;
+            ref_seqs.extend(
+                current
+                    .replace((name.into(), 0, md5::Context::new()))
+                    .map(finish),
+            );
+        } else if let Some((_, length, md5)) = &mut current {
+            let bases: Vec<u8> = line
+                .iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .map(u8::to_ascii_uppercase)
+                .collect();
+            *length += bases.len() as u64;
+            md5.consume(&bases);
+        }
+    }
+    ref_seqs.extend(current.map(finish));
+    Ok(ref_seqs)
+}
+
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut options = DictOptions {
+        assembly_id: None,
+        species: None,
+        uri: None,
+    };
+    let mut write_meta = true;
+    let mut output = None;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" => options.assembly_id = Some(value(&mut args, "-a")?),
+            "-s" => options.species = Some(value(&mut args, "-s")?),
+            "-u" => options.uri = Some(value(&mut args, "-u")?),
+            "-H" => write_meta = false,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(CommandError::Usage("only one FASTA file is allowed".into())),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar dict [-a assembly] [-s species] [-u uri] [-H] [-o out.dict] <ref.fa>".into(),
+        ));
+    };
+
+    let mut file = BufReader::new(fs::File::open(&path)?);
+    if options.uri.is_none() {
+        let absolute = Path::new(&path).canonicalize()?;
+        options.uri = Some(format!("file://{}", absolute.display()));
+    }
+    let compressed = bgzf::is_gzip(file.fill_buf()?);
+    let reader: Box<dyn BufRead> = if compressed {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(file)
+    };
+
+    let mut header = Header::default();
+    if write_meta {
+        header.set_meta(HeaderMeta::new(1, 0, Some(SortOrder::Unsorted)));
+    }
+    for ref_seq in read_ref_seqs(reader, &options)? {
+        header.add_reference_seq(ref_seq)?;
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::io::Write;
+
+use crate::{
+    commands::{CommandError, create_output, is_option, open_sam, unknown_option},
+    header::dictionary::{Difference, Relation, compare},
+    sam::validation::Stringency,
+};
+
+fn relation_str(relation: Relation) -> &'static str {
+    match relation {
+        Relation::Identical => "identical",
+        Relation::Reordered => "reordered",
+        Relation::Subset => "subset",
+        Relation::Superset => "superset",
+        Relation::Partial => "partial",
+        Relation::Renamed => "renamed",
+        Relation::Conflicting => "conflicting",
+    }
+}
+
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut paths = Vec::new();
+
+    for arg in args.by_ref() {
+        match arg.as_str() {
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    let [first_path, second_path] = paths.as_slice() else {
+        return Err(CommandError::Usage(
+            "samovar dictdiff <a.sam|a.dict> <b.sam|b.dict>".into(),
+        ));
+    };
+
+    let first = open_sam(first_path, Stringency::Silent)?;
+    let second = open_sam(second_path, Stringency::Silent)?;
+    let comparison = compare(first.header(), second.header());
+
+    // The relation, then one tab-separated line per difference
+    let mut out = create_output(None)?;
+    writeln!(out, "{}", relation_str(comparison.relation))?;
+    for difference in &comparison.differences {
+        match difference {
+            Difference::OnlyInFirst(seq) => {
+                writeln!(out, "first-only\t{}\t{}", seq.name(), seq.length())?;
+            }
+            Difference::OnlyInSecond(seq) => {
+                writeln!(out, "second-only\t{}\t{}", seq.name(), seq.length())?;
+            }
+            Difference::Renamed(a, b) => {
+                writeln!(out, "renamed\t{}\t{}", a.name(), b.name())?;
+            }
+            Difference::Moved(seq, from, to) => {
+                writeln!(out, "moved\t{}\t{}\t{}", seq.name(), from, to)?;
+            }
+            Difference::Length(a, b) => {
+                writeln!(out, "length\t{}\t{}\t{}", a.name(), a.length(), b.length())?;
+            }
+            Difference::Checksum(a, b) => {
+                writeln!(
+                    out,
+                    "checksum\t{}\t{}\t{}",
+                    a.name(),
+                    a.checksum().unwrap_or("*"),
+                    b.checksum().unwrap_or("*")
+                )?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
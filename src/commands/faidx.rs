@@ -0,0 +1,82 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use crate::{
+    alignment::sequence::reverse_complement,
+    bgzf,
+    commands::{CommandError, create_output, is_option, unknown_option, value},
+    fasta::{self, IndexedReader, index},
+    region::Region,
+};
+
+fn write_index(path: &str) -> Result<(), CommandError> {
+    let (records, blocks) = fasta::build_index(path)?;
+    let mut fai = BufWriter::new(fs::File::create(fasta::index_path(path.as_ref(), "fai"))?);
+    index::write(&mut fai, &records)?;
+    fai.flush()?;
+    if let Some(blocks) = blocks {
+        let mut gzi = BufWriter::new(fs::File::create(fasta::index_path(path.as_ref(), "gzi"))?);
+        bgzf::write_gzi(&mut gzi, &blocks)?;
+        gzi.flush()?;
+    }
+    Ok(())
+}
+
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut reverse = false;
+    let mut width = 60;
+    let mut output = None;
+    let mut path = None;
+    let mut regions = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" => reverse = true,
+            "-n" => width = value(&mut args, "-n")?,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            "-r" => {
+                let file: String = value(&mut args, "-r")?;
+                for line in BufReader::new(fs::File::open(file)?).lines() {
+                    let line = line?;
+                    if !line.is_empty() {
+                        regions.push(line);
+                    }
+                }
+            }
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => regions.push(arg),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar faidx [-i] [-n width] [-r regions.txt] [-o out] <ref.fa> [region...]".into(),
+        ));
+    };
+
+    if regions.is_empty() {
+        return write_index(&path);
+    }
+
+    let mut reader = IndexedReader::open(&path)?;
+    let mut out = create_output(output.as_deref())?;
+    for name in regions {
+        let region: Region = name.parse()?;
+        let mut seq = reader.fetch(&region)?;
+        if reverse {
+            seq = reverse_complement(&seq);
+            writeln!(out, ">{name}/rc")?;
+        } else {
+            writeln!(out, ">{name}")?;
+        }
+        // A width of 0 writes each sequence on a single line
+        for line in seq.chunks(if width == 0 { seq.len().max(1) } else { width }) {
+            out.write_all(line)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use std::io::Write;
+
+use flate2::{Compression, write::GzEncoder};
+
+use crate::{
+    alignment::Alignment,
+    commands::{
+        CommandError, create_output, flags_value, is_option, open_sam, unknown_option, value,
+    },
+    sam::validation::Stringency,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Format {
+    Fasta,
+    Fastq,
+}
+
+/// Where a read is written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route {
+    Read1,
+    Read2,
+    /// Reads flagged as both or neither of READ1 and READ2
+    Other,
+    /// Paired reads whose mate is not in the input
+    Singleton,
+}
+
+enum Sink {
+    Plain(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+}
+
+impl Sink {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::Plain(w) => w,
+            Self::Gzip(w) => w,
+        }
+    }
+
+    fn finish(self) -> Result<(), CommandError> {
+        match self {
+            Self::Plain(mut w) => w.flush()?,
+            Self::Gzip(w) => w.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+struct Outputs {
+    paths: Vec<String>,
+    sinks: Vec<Sink>,
+    // Index into `sinks` for each route
+    routes: [usize; 4],
+}
+
+impl Outputs {
+    /// Opens the outputs, sharing a sink between routes given the same path.
+    ///
+    /// Routes without a path go to stdout, except singletons which go with their mates.
+    fn open(paths: [Option<String>; 4], gzip: bool) -> Result<Self, CommandError> {
+        let mut outputs = Self {
+            paths: Vec::new(),
+            sinks: Vec::new(),
+            routes: [0; 4],
+        };
+        for (route, path) in paths.into_iter().enumerate() {
+            let path = match path {
+                Some(path) => path,
+                None if route == Route::Singleton as usize => {
+                    outputs.routes[route] = usize::MAX;
+                    continue;
+                }
+                None => "-".into(),
+            };
+            outputs.routes[route] = match outputs.paths.iter().position(|p| *p == path) {
+                Some(i) => i,
+                None => {
+                    let file = create_output(Some(&path))?;
+                    outputs.sinks.push(if gzip || path.ends_with(".gz") {
+                        Sink::Gzip(GzEncoder::new(file, Compression::default()))
+                    } else {
+                        Sink::Plain(file)
+                    });
+                    outputs.paths.push(path);
+                    outputs.sinks.len() - 1
+                }
+            };
+        }
+        Ok(outputs)
+    }
+
+    fn get(&mut self, route: Route, segment: Route) -> &mut dyn Write {
+        let mut i = self.routes[route as usize];
+        if i == usize::MAX {
+            i = self.routes[segment as usize];
+        }
+        self.sinks[i].writer()
+    }
+
+    fn finish(self) -> Result<(), CommandError> {
+        self.sinks.into_iter().try_for_each(Sink::finish)
+    }
+}
+
+struct FastqOptions {
+    format: Format,
+    read_number_suffix: bool,
+    tags: Vec<[u8; 2]>,
+    default_quality: u8,
+}
+
+fn write_read(
+    out: &mut dyn Write,
+    alignment: &Alignment,
+    segment: Route,
+    options: &FastqOptions,
+) -> Result<(), CommandError> {
+    let marker = match options.format {
+        Format::Fasta => '>',
+        Format::Fastq => '@',
+    };
+    write!(out, "{marker}{}", alignment.query_name())?;
+    if options.read_number_suffix {
+        match segment {
+            Route::Read1 => write!(out, "/1")?,
+            Route::Read2 => write!(out, "/2")?,
+            _ => {}
+        }
+    }
+    for tag in options.tags.iter().filter_map(|t| alignment.tag(t)) {
+        write!(out, "\t{tag}")?;
+    }
+    writeln!(out)?;
+
+    let reverse = alignment.flag().is_reverse_complement();
+    let reversed;
+    let sequence = if reverse {
+        reversed = alignment.sequence().reverse_complement();
+        &reversed
+    } else {
+        alignment.sequence()
+    };
+    out.write_all(sequence.as_bytes())?;
+    writeln!(out)?;
+
+    if options.format == Format::Fastq {
+        writeln!(out, "+")?;
+        match alignment.quality() {
+            None => {
+                let quality = vec![options.default_quality.min(93) + 33; sequence.len()];
+                out.write_all(&quality)?;
+            }
+            Some(q) if reverse => out.write_all(&q.reversed().to_ascii(33))?,
+            Some(q) => out.write_all(&q.to_ascii(33))?,
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// The output of a read based on its READ1 and READ2 flags.
+fn segment_of(alignment: &Alignment) -> Route {
+    let flag = alignment.flag();
+    match (flag.is_first_segment(), flag.is_last_segment()) {
+        (true, false) => Route::Read1,
+        (false, true) => Route::Read2,
+        _ => Route::Other,
+    }
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    format: Format,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut options = FastqOptions {
+        format,
+        read_number_suffix: true,
+        tags: Vec::new(),
+        default_quality: 1,
+    };
+    let mut include_flags = 0;
+    let mut exclude_flags = 0x900;
+    let mut gzip = false;
+    // Indexed by `Route`
+    let mut paths: [Option<String>; 4] = Default::default();
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-1" => paths[Route::Read1 as usize] = Some(value(&mut args, "-1")?),
+            "-2" => paths[Route::Read2 as usize] = Some(value(&mut args, "-2")?),
+            "-0" => paths[Route::Other as usize] = Some(value(&mut args, "-0")?),
+            "-s" => paths[Route::Singleton as usize] = Some(value(&mut args, "-s")?),
+            "-o" => {
+                let output: String = value(&mut args, "-o")?;
+                for p in &mut paths[..3] {
+                    p.get_or_insert_with(|| output.clone());
+                }
+            }
+            "-f" => include_flags = flags_value(&mut args, "-f")?,
+            "-F" => exclude_flags = flags_value(&mut args, "-F")?,
+            "-n" => options.read_number_suffix = false,
+            "-T" => {
+                let tags: String = value(&mut args, "-T")?;
+                for tag in tags.split(',') {
+                    match tag.as_bytes() {
+                        &[a, b] => options.tags.push([a, b]),
+                        _ => return Err(CommandError::Usage(format!("bad tag {tag}"))),
+                    }
+                }
+            }
+            "-v" => options.default_quality = value(&mut args, "-v")?,
+            "-z" => gzip = true,
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        let command = match format {
+            Format::Fasta => "fasta",
+            Format::Fastq => "fastq",
+        };
+        return Err(CommandError::Usage(format!(
+            "samovar {command} [-1 r1] [-2 r2] [-0 other] [-s singletons] [-o out] [-f flags] [-F flags] [-n] [-T tags] [-v quality] [-z] <in.sam>"
+        )));
+    };
+
+    let reader = open_sam(&path, stringency)?;
+    let mut outputs = Outputs::open(paths, gzip)?;
+    // A READ1 or READ2 read waiting for its mate, which directly follows it in collated input
+    let mut pending: Option<Alignment> = None;
+    for alignment in reader {
+        let alignment = alignment?;
+        let bits = alignment.flag().bits();
+        if bits & include_flags != include_flags || bits & exclude_flags != 0 {
+            continue;
+        }
+
+        let segment = segment_of(&alignment);
+        if segment == Route::Other {
+            write_read(
+                outputs.get(Route::Other, segment),
+                &alignment,
+                segment,
+                &options,
+            )?;
+            continue;
+        }
+        match pending.take() {
+            Some(mate)
+                if mate.query_name() == alignment.query_name() && segment_of(&mate) != segment =>
+            {
+                let (read1, read2) = match segment {
+                    Route::Read2 => (mate, alignment),
+                    _ => (alignment, mate),
+                };
+                write_read(
+                    outputs.get(Route::Read1, Route::Read1),
+                    &read1,
+                    Route::Read1,
+                    &options,
+                )?;
+                write_read(
+                    outputs.get(Route::Read2, Route::Read2),
+                    &read2,
+                    Route::Read2,
+                    &options,
+                )?;
+            }
+            Some(single) => {
+                let s = segment_of(&single);
+                write_read(outputs.get(Route::Singleton, s), &single, s, &options)?;
+                pending = Some(alignment);
+            }
+            None => pending = Some(alignment),
+        }
+    }
+    if let Some(single) = pending {
+        let s = segment_of(&single);
+        write_read(outputs.get(Route::Singleton, s), &single, s, &options)?;
+    }
+    outputs.finish()
+}
@@ -0,0 +1,191 @@
+use std::io::Write;
+
+use crate::{
+    alignment::{Alignment, Flag, position::Position, tag::TagValue, writer::write_alignment},
+    commands::{
+        CommandError, add_program_record, create_output, is_option, open_sam, unknown_option, value,
+    },
+    header::writer::write_header,
+    markdup::quality_score,
+    sam::validation::Stringency,
+};
+
+/// What a segment learns about its mate, taken from the mate's primary alignment.
+struct MateInfo {
+    ref_seq_name: String,
+    pos: Option<Position>,
+    is_reverse: bool,
+    is_unmapped: bool,
+    cigar: String,
+    score: i64,
+}
+
+impl MateInfo {
+    fn new(mate: &Alignment) -> Self {
+        Self {
+            ref_seq_name: mate.ref_seq_name().to_owned(),
+            pos: mate.position(),
+            is_reverse: mate.flag().is_reverse_complement(),
+            is_unmapped: mate.flag().is_unmapped(),
+            cigar: mate.cigar().to_string(),
+            score: quality_score(mate),
+        }
+    }
+}
+
+/// Signed observed template length of two mapped mates on the same reference, positive for the
+/// leftmost one.
+fn template_len(read1: &Alignment, read2: &Alignment) -> (i32, i32) {
+    if read1.flag().is_unmapped()
+        || read2.flag().is_unmapped()
+        || read1.ref_seq_name() != read2.ref_seq_name()
+    {
+        return (0, 0);
+    }
+    let (Some(start1), Some(start2), Some(end1), Some(end2)) =
+        (read1.position(), read2.position(), read1.end(), read2.end())
+    else {
+        return (0, 0);
+    };
+    let len = (end1.max(end2).get() - start1.min(start2).get() + 1) as i32;
+    if start1 <= start2 {
+        (len, -len)
+    } else {
+        (-len, len)
+    }
+}
+
+fn apply_mate(alignment: &mut Alignment, mate: &MateInfo, template_len: i32) {
+    let rnext = if mate.ref_seq_name == "*" {
+        "*".to_owned()
+    } else if mate.ref_seq_name == alignment.ref_seq_name() {
+        "=".to_owned()
+    } else {
+        mate.ref_seq_name.clone()
+    };
+    alignment.set_mate(rnext, mate.pos, template_len);
+
+    let is_unmapped = alignment.flag().is_unmapped();
+    let flag = alignment.flag_mut();
+    flag.set(Flag::MREVERSE, mate.is_reverse);
+    flag.set(Flag::MUNMAP, mate.is_unmapped);
+    if mate.is_unmapped || is_unmapped {
+        flag.set(Flag::PROPER_PAIR, false);
+    }
+
+    if mate.is_unmapped {
+        alignment.remove_tag(b"MC");
+    } else {
+        alignment.set_tag(*b"MC", TagValue::String(mate.cigar.clone()));
+    }
+    alignment.set_tag(*b"ms", TagValue::Int(mate.score));
+}
+
+/// Makes the mate fields of all alignments of a template agree with its primary READ1 and READ2
+/// alignments.
+///
+/// Templates without both primary alignments are left unchanged.
+fn fix_template(group: &mut [Alignment]) {
+    let primary = |segment: u16| {
+        group.iter().position(|a| {
+            let flag = a.flag();
+            flag.has_multiple_segments() && flag.is_primary_line() && flag.bits() & 0xc0 == segment
+        })
+    };
+    let (Some(i1), Some(i2)) = (primary(0x40), primary(0x80)) else {
+        return;
+    };
+
+    // Unmapped reads are placed at their mapped mate
+    for (unmapped, mapped) in [(i1, i2), (i2, i1)] {
+        if group[unmapped].flag().is_unmapped() && !group[mapped].flag().is_unmapped() {
+            let ref_seq_name = group[mapped].ref_seq_name().to_owned();
+            let pos = group[mapped].position();
+            group[unmapped].set_position(ref_seq_name, pos);
+        }
+    }
+
+    let (len1, len2) = template_len(&group[i1], &group[i2]);
+    let mate_of_read1 = MateInfo::new(&group[i2]);
+    let mate_of_read2 = MateInfo::new(&group[i1]);
+    for (i, alignment) in group.iter_mut().enumerate() {
+        let (mate, len) = match alignment.flag().bits() & 0xc0 {
+            0x40 => (&mate_of_read1, len1),
+            0x80 => (&mate_of_read2, len2),
+            _ => continue,
+        };
+        let len = if i == i1 || i == i2 {
+            len
+        } else {
+            alignment.template_len()
+        };
+        apply_mate(alignment, mate, len);
+    }
+}
+
+/// Fixes and writes the alignments of a template, leaving `group` empty.
+fn write_template(
+    out: &mut impl Write,
+    group: &mut Vec<Alignment>,
+    remove: bool,
+) -> Result<(), CommandError> {
+    fix_template(group);
+    for alignment in group.drain(..) {
+        let flag = alignment.flag();
+        if remove && (flag.is_unmapped() || flag.is_secondary_alignment()) {
+            continue;
+        }
+        write_alignment(out, &alignment)?;
+    }
+    Ok(())
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut remove = false;
+    let mut output = None;
+    let mut no_pg = false;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" => remove = true,
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar fixmate [-r] [--no-PG] [-o out] <in.sam>".into(),
+        ));
+    };
+
+    let reader = open_sam(&path, stringency)?;
+    let mut header = reader.header().clone();
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+
+    // Alignments of the current template, which are adjacent in name-collated input
+    let mut group: Vec<Alignment> = Vec::new();
+    for alignment in reader {
+        let alignment = alignment?;
+        if group
+            .first()
+            .is_some_and(|a| a.query_name() != alignment.query_name())
+        {
+            write_template(&mut out, &mut group, remove)?;
+        }
+        group.push(alignment);
+    }
+    write_template(&mut out, &mut group, remove)?;
+    out.flush()?;
+    Ok(())
+}
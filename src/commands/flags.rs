@@ -0,0 +1,35 @@
+use std::io::Write;
+
+use crate::{
+    alignment::{Flag, flag::NAMES},
+    commands::{CommandError, create_output},
+};
+
+/// Decodes or encodes each flag given as a number or as comma-separated names, printing it in
+/// hexadecimal, decimal and by name as `samtools flags` does.
+pub(super) fn run(args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let flags = args
+        .map(|arg| arg.parse::<Flag>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if flags.is_empty() {
+        let mut usage = String::from("samovar flags <flag|NAME,NAME,...>...");
+        for (mask, name) in NAMES {
+            usage.push_str(&format!("\n{mask:#x}\t{name}"));
+        }
+        return Err(CommandError::Usage(usage));
+    }
+
+    let mut out = create_output(None)?;
+    for flag in flags {
+        writeln!(out, "{:#x}\t{}\t{flag}", flag.bits(), flag.bits())?;
+        for problem in flag.problems() {
+            eprintln!(
+                "samovar: warning: {:#x} is invalid: {}",
+                flag.bits(),
+                problem.name()
+            );
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+};
+
+use flate2::bufread::MultiGzDecoder;
+
+use crate::{
+    alignment::{
+        Alignment, Flag,
+        flag::FlagBuilder,
+        sequence::Quality,
+        tag::{Tag, TagValue},
+        writer::write_alignment,
+    },
+    bgzf,
+    commands::{CommandError, add_program_record, create_output, is_option, unknown_option, value},
+    fastq::{self, FastqRecord},
+    header::{Header, HeaderMeta, ReadGroup, SortOrder, parser::ParseError, writer::write_header},
+};
+
+/// Opens a plain or gzip-compressed FASTQ file, with `-` meaning stdin.
+fn open_fastq(path: &str) -> Result<fastq::Reader<Box<dyn BufRead>>, CommandError> {
+    let mut reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(fs::File::open(path)?))
+    };
+    if bgzf::is_gzip(reader.fill_buf()?) {
+        reader = Box::new(BufReader::new(MultiGzDecoder::new(reader)));
+    }
+    Ok(fastq::Reader::new(reader))
+}
+
+/// Fields of a Casava 1.8 comment, `<read>:<is filtered>:<control number>:<index>`.
+struct Casava<'a> {
+    is_filtered: bool,
+    index: &'a str,
+}
+
+fn parse_casava(s: &str) -> Option<Casava<'_>> {
+    let mut fields = s.splitn(4, ':');
+    let read = fields.next()?;
+    let is_filtered = match fields.next()? {
+        "Y" => true,
+        "N" => false,
+        _ => return None,
+    };
+    let control = fields.next()?;
+    let index = fields.next()?;
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    (is_number(read) && is_number(control)).then_some(Casava { is_filtered, index })
+}
+
+/// Builds an unmapped record, taking the QC-fail flag and tags from the FASTQ comment.
+///
+/// Comment fields are either SAM tags, as written by `samovar fastq -T`, or Casava fields whose
+/// index sequence is stored as BC.
+fn to_alignment(
+    record: FastqRecord,
+    mut flag: FlagBuilder,
+    read_group: Option<&str>,
+) -> Result<Alignment, ParseError> {
+    let mut tags = Vec::new();
+    for field in record.comment.iter().flat_map(|c| c.split_whitespace()) {
+        if let Ok(tag) = field.parse::<Tag>() {
+            tags.push(tag);
+        } else if let Some(casava) = parse_casava(field) {
+            if casava.is_filtered {
+                flag = flag.qc_fail();
+            }
+            let is_barcode = |b: u8| b.is_ascii_alphabetic() || b == b'-' || b == b'+';
+            if !casava.index.is_empty() && casava.index.bytes().all(is_barcode) {
+                tags.push(Tag {
+                    name: *b"BC",
+                    value: TagValue::String(casava.index.replace('+', "-")),
+                });
+            }
+        }
+    }
+
+    let mut alignment = Alignment::unmapped(
+        base_name(&record.name).into(),
+        flag.build()?,
+        record.sequence.parse()?,
+        Some(Quality::from_ascii(record.quality.as_bytes(), 33)?),
+    );
+    if let Some(id) = read_group {
+        alignment.set_tag(*b"RG", TagValue::String(id.into()));
+    }
+    for tag in tags {
+        alignment.set_tag(tag.name, tag.value);
+    }
+    Ok(alignment)
+}
+
+/// Read name without a trailing `/1` or `/2`.
+fn base_name(name: &str) -> &str {
+    name.strip_suffix("/1")
+        .or_else(|| name.strip_suffix("/2"))
+        .unwrap_or(name)
+}
+
+fn next_mate(
+    reader: &mut fastq::Reader<Box<dyn BufRead>>,
+    first: &FastqRecord,
+) -> Result<FastqRecord, CommandError> {
+    let mate = reader
+        .next()
+        .ok_or_else(|| CommandError::MismatchedMates(first.name.clone()))??;
+    if base_name(&first.name) != base_name(&mate.name) {
+        return Err(CommandError::MismatchedMates(first.name.clone()));
+    }
+    Ok(mate)
+}
+
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut read1 = None;
+    let mut read2 = None;
+    let mut single = None;
+    let mut interleaved = false;
+    let mut read_group_id = None;
+    let mut read_group_fields = Vec::new();
+    let mut output = None;
+    let mut no_pg = false;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-1" => read1 = Some(value::<String>(&mut args, "-1")?),
+            "-2" => read2 = Some(value::<String>(&mut args, "-2")?),
+            "-s" => single = Some(value::<String>(&mut args, "-s")?),
+            "-i" => interleaved = true,
+            "-R" => read_group_id = Some(value::<String>(&mut args, "-R")?),
+            "-r" => read_group_fields.push(value::<String>(&mut args, "-r")?),
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    match paths.len() {
+        0 => {}
+        1 if single.is_none() && read1.is_none() => single = paths.pop(),
+        2 if read1.is_none() && read2.is_none() => {
+            read2 = paths.pop();
+            read1 = paths.pop();
+        }
+        _ => return Err(CommandError::Usage("too many input files".into())),
+    }
+    if (single.is_none() && read1.is_none()) || read1.is_some() != read2.is_some() {
+        return Err(CommandError::Usage(
+            "samovar import [-i] [-R id] [-r TAG:value]... [--no-PG] [-o out] (<in.fq> | <r1.fq> <r2.fq> | -1 r1.fq -2 r2.fq [-s single.fq])".into(),
+        ));
+    }
+
+    let mut header = Header::default();
+    header.set_meta(HeaderMeta::new(1, 6, Some(SortOrder::Unsorted)));
+    if read_group_id.is_some() || !read_group_fields.is_empty() {
+        let mut fields = read_group_fields;
+        if let Some(id) = &read_group_id {
+            fields.insert(0, format!("ID:{id}"));
+        }
+        let read_group: ReadGroup = fields.join("\t").parse()?;
+        read_group_id = Some(read_group.id().to_owned());
+        header.add_read_group(read_group)?;
+    }
+    let read_group = read_group_id.as_deref();
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+
+    let paired = Flag::builder().paired().unmapped().mate_unmapped();
+    if let (Some(read1), Some(read2)) = (read1, read2) {
+        let mut reader2 = open_fastq(&read2)?;
+        for record in open_fastq(&read1)? {
+            let record = record?;
+            let mate = next_mate(&mut reader2, &record)?;
+            write_alignment(&mut out, &to_alignment(record, paired.read1(), read_group)?)?;
+            write_alignment(&mut out, &to_alignment(mate, paired.read2(), read_group)?)?;
+        }
+        if let Some(extra) = reader2.next() {
+            return Err(CommandError::MismatchedMates(extra?.name));
+        }
+    }
+    if let Some(single) = single {
+        let mut reader = open_fastq(&single)?;
+        while let Some(record) = reader.next() {
+            let record = record?;
+            if interleaved {
+                let mate = next_mate(&mut reader, &record)?;
+                write_alignment(&mut out, &to_alignment(record, paired.read1(), read_group)?)?;
+                write_alignment(&mut out, &to_alignment(mate, paired.read2(), read_group)?)?;
+            } else {
+                write_alignment(
+                    &mut out,
+                    &to_alignment(record, Flag::builder().unmapped(), read_group)?,
+                )?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
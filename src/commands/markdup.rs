@@ -0,0 +1,157 @@
+use std::{fs, io::Write};
+
+use crate::{
+    alignment::{Alignment, Flag, tag::TagValue, writer::write_alignment},
+    commands::{
+        CommandError, add_program_record, create_output, is_option, open_sam, unknown_option, value,
+    },
+    header::writer::write_header,
+    markdup::{DuplicateFinder, DuplicateKind, DuplicateOptions, DuplicateStats, umi::UmiMethod},
+    sam::validation::Stringency,
+};
+
+/// Tag holding the number of templates of a molecule when grouping by UMI.
+const GROUP_SIZE_TAG: [u8; 2] = *b"gs";
+
+fn write_stats(
+    w: &mut impl Write,
+    stats: &DuplicateStats,
+    written: u64,
+) -> Result<(), CommandError> {
+    writeln!(w, "READ: {}", stats.read)?;
+    writeln!(w, "WRITTEN: {written}")?;
+    writeln!(w, "EXCLUDED: {}", stats.excluded)?;
+    writeln!(w, "EXAMINED: {}", stats.examined)?;
+    writeln!(w, "PAIRED: {}", stats.paired)?;
+    writeln!(w, "SINGLE: {}", stats.single)?;
+    writeln!(w, "DUPLICATE PAIR: {}", stats.duplicate_pair)?;
+    writeln!(w, "DUPLICATE SINGLE: {}", stats.duplicate_single)?;
+    writeln!(
+        w,
+        "DUPLICATE PAIR OPTICAL: {}",
+        stats.duplicate_pair_optical
+    )?;
+    writeln!(
+        w,
+        "DUPLICATE SINGLE OPTICAL: {}",
+        stats.duplicate_single_optical
+    )?;
+    writeln!(w, "DUPLICATE TOTAL: {}", stats.duplicate_total())?;
+    writeln!(w, "PERCENT_DUPLICATION: {:.6}", stats.percent_duplication())?;
+    match stats.estimated_library_size() {
+        Some(size) => writeln!(w, "ESTIMATED_LIBRARY_SIZE: {size}")?,
+        None => writeln!(w, "ESTIMATED_LIBRARY_SIZE: NA")?,
+    }
+    Ok(())
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut options = DuplicateOptions::default();
+    let mut umi_method = None;
+    let mut umi_distance = 1;
+    let mut remove = false;
+    let mut report = false;
+    let mut stats_path = None;
+    let mut output = None;
+    let mut no_pg = false;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" => remove = true,
+            "-d" => options.optical_distance = Some(value(&mut args, "-d")?),
+            "-s" => report = true,
+            "--umi" => umi_method = Some(value::<String>(&mut args, "--umi")?),
+            "--umi-distance" => umi_distance = value(&mut args, "--umi-distance")?,
+            "--umi-tag" => {
+                let tag: String = value(&mut args, "--umi-tag")?;
+                options.umi_tag = match tag.as_bytes() {
+                    &[a, b] => [a, b],
+                    _ => return Err(CommandError::Usage(format!("bad tag {tag}"))),
+                };
+            }
+            "--duplex" => options.duplex = true,
+            "-f" => stats_path = Some(value::<String>(&mut args, "-f")?),
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar markdup [-r] [-d opticalDistance] [-s] [-f stats.txt] [--umi exact|edit|directional] [--umi-distance n] [--umi-tag RX] [--duplex] [--no-PG] [-o out] <in.sam>"
+                .into(),
+        ));
+    };
+    options.umi = match umi_method.as_deref() {
+        None => None,
+        Some("exact") => Some(UmiMethod::Exact),
+        Some("edit") => Some(UmiMethod::EditDistance(umi_distance)),
+        Some("directional") => Some(UmiMethod::Directional),
+        Some(other) => {
+            return Err(CommandError::Usage(format!("unknown UMI method {other}")));
+        }
+    };
+    let optical_distance = options.optical_distance;
+
+    let reader = open_sam(&path, stringency)?;
+    let mut header = reader.header().clone();
+    // Duplicates of a template can be anywhere in the input, so all of it is kept in memory
+    let alignments: Vec<Alignment> = reader.collect::<Result<_, _>>()?;
+    let mut finder = DuplicateFinder::new(&header, options);
+    for alignment in &alignments {
+        finder.add(alignment)?;
+    }
+    let duplicates = finder.finish();
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+    let mut written = 0;
+    for mut alignment in alignments {
+        let kind = duplicates.kinds.get(alignment.query_name()).copied();
+        if remove && kind.is_some() {
+            continue;
+        }
+        alignment.flag_mut().set(Flag::DUP, kind.is_some());
+        if let Some(molecule) = duplicates.molecules.get(alignment.query_name()) {
+            alignment.set_tag(*b"MI", TagValue::String(molecule.identifier()));
+            alignment.set_tag(GROUP_SIZE_TAG, TagValue::Int(molecule.size as i64));
+        }
+        if optical_distance.is_some() {
+            match kind {
+                Some(DuplicateKind::Optical) => {
+                    alignment.set_tag(*b"dt", TagValue::String("SQ".into()))
+                }
+                Some(DuplicateKind::Library) => {
+                    alignment.set_tag(*b"dt", TagValue::String("LB".into()))
+                }
+                None => {
+                    alignment.remove_tag(b"dt");
+                }
+            }
+        }
+        write_alignment(&mut out, &alignment)?;
+        written += 1;
+    }
+    out.flush()?;
+
+    if report {
+        write_stats(&mut std::io::stderr().lock(), &duplicates.stats, written)?;
+    }
+    if let Some(stats_path) = stats_path {
+        write_stats(
+            &mut fs::File::create(stats_path)?,
+            &duplicates.stats,
+            written,
+        )?;
+    }
+    Ok(())
+}
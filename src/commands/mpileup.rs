@@ -0,0 +1,231 @@
+use std::{
+    fs,
+    io::{BufReader, Write},
+};
+
+use crate::{
+    alignment::{Alignment, position::Position},
+    commands::{
+        CommandError, create_output, flags_value, is_option, open_sam, unknown_option, value,
+    },
+    depth::DepthOptions,
+    fasta::IndexedReader,
+    pileup::{OverlapMode, Pileup, PileupColumn, PileupEntry},
+    region::{Region, read_bed},
+    sam::{merge::Merged, validation::Stringency},
+};
+
+struct MpileupOptions {
+    min_base_quality: u8,
+    output_map_quality: bool,
+    region: Option<Region>,
+    bed: Option<Vec<Region>>,
+}
+
+impl MpileupOptions {
+    fn includes(&self, name: &str, pos: u32) -> bool {
+        self.region.as_ref().is_none_or(|r| r.contains(name, pos))
+            && self
+                .bed
+                .as_ref()
+                .is_none_or(|bed| bed.iter().any(|r| r.contains(name, pos)))
+    }
+}
+
+/// Base at the 1-based position `pos` of the current reference sequence, `N` if unknown.
+fn reference_base(reference: &[u8], pos: u32) -> u8 {
+    Position::new(pos)
+        .and_then(|pos| reference.get(pos.to_offset()))
+        .map_or(b'N', |b| b.to_ascii_uppercase())
+}
+
+/// MAPQ as a Phred+33 character, where an unavailable MAPQ shows as the highest, `~`.
+fn map_quality_char(alignment: &Alignment) -> char {
+    char::from(alignment.mapping_quality().map_or(93, |q| q.min(93)) + 33)
+}
+
+fn write_entry(bases: &mut String, entry: &PileupEntry, reference: &[u8], pos: u32, ref_base: u8) {
+    let reverse = entry.alignment.flag().is_reverse_complement();
+    let stranded = |c: char| {
+        if reverse {
+            c.to_ascii_lowercase()
+        } else {
+            c.to_ascii_uppercase()
+        }
+    };
+
+    if entry.is_head {
+        bases.push('^');
+        bases.push(map_quality_char(&entry.alignment));
+    }
+    if entry.is_del {
+        bases.push('*');
+    } else if entry.is_refskip {
+        bases.push(if reverse { '<' } else { '>' });
+    } else {
+        let base = entry.base().unwrap_or(b'N');
+        if ref_base != b'N' && base.eq_ignore_ascii_case(&ref_base) {
+            bases.push(if reverse { ',' } else { '.' });
+        } else {
+            bases.push(stranded(char::from(base)));
+        }
+    }
+
+    if entry.indel > 0 {
+        bases.push('+');
+        bases.push_str(&entry.indel.to_string());
+        bases.extend(entry.inserted_bases().chars().map(stranded));
+    } else if entry.indel < 0 {
+        bases.push_str(&entry.indel.to_string());
+        let deleted = pos + 1..=pos + entry.indel.unsigned_abs();
+        bases.extend(deleted.map(|p| stranded(char::from(reference_base(reference, p)))));
+    }
+    if entry.is_tail {
+        bases.push('$');
+    }
+}
+
+fn write_column(
+    out: &mut impl Write,
+    name: &str,
+    reference: &[u8],
+    column: &PileupColumn,
+    inputs: usize,
+    options: &MpileupOptions,
+) -> Result<(), CommandError> {
+    let ref_base = reference_base(reference, column.pos);
+    write!(out, "{name}\t{}\t{}", column.pos, char::from(ref_base))?;
+
+    for input in 0..inputs {
+        let mut depth = 0;
+        let mut bases = String::new();
+        let mut qualities = String::new();
+        let mut map_qualities = String::new();
+
+        for entry in column.entries.iter().filter(|e| e.input == input) {
+            let quality = entry.quality;
+            let is_base = !entry.is_del && !entry.is_refskip;
+            if is_base && quality.is_some_and(|q| q < options.min_base_quality) {
+                continue;
+            }
+            depth += 1;
+            write_entry(&mut bases, entry, reference, column.pos, ref_base);
+            qualities.push(char::from(quality.unwrap_or(93).min(93) + 33));
+            map_qualities.push(map_quality_char(&entry.alignment));
+        }
+
+        if depth == 0 {
+            // Matches samtools, which never writes empty fields
+            bases.push('*');
+            qualities.push('*');
+            map_qualities.push('*');
+        }
+        write!(out, "\t{depth}\t{bases}\t{qualities}")?;
+        if options.output_map_quality {
+            write!(out, "\t{map_qualities}")?;
+        }
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut filter = DepthOptions::default();
+    let mut options = MpileupOptions {
+        min_base_quality: 13,
+        output_map_quality: false,
+        region: None,
+        bed: None,
+    };
+    let mut fasta = None;
+    let mut max_depth = 8000;
+    let mut overlaps = OverlapMode::ZeroQuality;
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" => fasta = Some(IndexedReader::open(value::<String>(&mut args, "-f")?)?),
+            "-r" => options.region = Some(value(&mut args, "-r")?),
+            "-l" => {
+                let path: String = value(&mut args, "-l")?;
+                options.bed = Some(read_bed(BufReader::new(fs::File::open(path)?))?);
+            }
+            "-q" => filter.min_map_quality = value(&mut args, "-q")?,
+            "-Q" => options.min_base_quality = value(&mut args, "-Q")?,
+            "-d" => max_depth = value(&mut args, "-d")?,
+            "-s" => options.output_map_quality = true,
+            "-x" => overlaps = OverlapMode::Count,
+            "--overlaps" => {
+                overlaps = match value::<String>(&mut args, "--overlaps")?.as_str() {
+                    "count" => OverlapMode::Count,
+                    "best" => OverlapMode::KeepBest,
+                    "zero" => OverlapMode::ZeroQuality,
+                    other => {
+                        return Err(CommandError::Usage(format!("unknown overlap mode {other}")));
+                    }
+                }
+            }
+            "--rf" => filter.include_flags = flags_value(&mut args, "--rf")?,
+            "--ff" => filter.exclude_flags = flags_value(&mut args, "--ff")?,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(CommandError::Usage(
+            "samovar mpileup [-f ref.fa] [-r region] [-l regions.bed] [-q minMQ] [-Q minBQ] [-d maxDepth] [-s] [-x | --overlaps count|best|zero] [--rf flags] [--ff flags] [-o out] <in.sam>...".into(),
+        ));
+    }
+
+    let readers = paths
+        .iter()
+        .map(|p| open_sam(p, stringency))
+        .collect::<Result<_, _>>()?;
+    let merged = Merged::new(readers);
+    let header = merged.header().clone();
+    let alignments = merged.filter(|r| r.as_ref().map_or(true, |(_, a)| filter.accepts(a)));
+    let mut pileup = Pileup::new(header, alignments).with_overlaps(overlaps);
+    if max_depth > 0 {
+        pileup = pileup.with_max_depth(max_depth);
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    // Bases of the reference the pileup is on, fetched whole when the pileup moves to it
+    let mut reference = (None, Vec::new());
+    while let Some(column) = pileup.next() {
+        let column = column?;
+        let name = pileup
+            .header()
+            .reference_seq_at(column.ref_index)
+            .unwrap()
+            .name()
+            .to_owned();
+        if reference.0 != Some(column.ref_index)
+            && let Some(fasta) = &mut fasta
+        {
+            // References missing from the FASTA are treated as all N
+            let bases = match fasta.length(&name) {
+                Some(_) => fasta.fetch(&Region::whole(&name))?,
+                None => Vec::new(),
+            };
+            reference = (Some(column.ref_index), bases);
+        }
+        if options.includes(&name, column.pos) {
+            write_column(
+                &mut out,
+                &name,
+                &reference.1,
+                &column,
+                paths.len(),
+                &options,
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use crate::{
+    commands::{CommandError, create_output, is_option, open_sam, unknown_option},
+    header::provenance::ProgramGraph,
+    sam::validation::Stringency,
+};
+
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut topological = false;
+    let mut path = None;
+
+    for arg in args.by_ref() {
+        match arg.as_str() {
+            "-t" => topological = true,
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar provenance [-t] <in.sam>".into(),
+        ));
+    };
+
+    let reader = open_sam(&path, Stringency::Silent)?;
+    let graph = ProgramGraph::new(reader.header())?;
+    let mut out = create_output(None)?;
+    // -t lists the program IDs alone, each after the program it follows
+    if topological {
+        for program in graph.topological() {
+            writeln!(out, "{}", program.id())?;
+        }
+    } else {
+        write!(out, "{graph}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
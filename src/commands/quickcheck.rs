@@ -0,0 +1,351 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+use flate2::{bufread::MultiGzDecoder, read::GzDecoder};
+
+use crate::{
+    bgzf,
+    commands::{CommandError, create_output, is_option, unknown_option},
+    header::Header,
+    sam::{order::OrderChecked, reader::Reader},
+};
+
+/// The EOF container ending a CRAM 3 file.
+const CRAM3_EOF: [u8; 38] = [
+    0x0f, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x0f, 0xe0, 0x45, 0x4f, 0x46, 0x00, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x05, 0xbd, 0xd9, 0x4f, 0x00, 0x01, 0x00, 0x06, 0x06, 0x01, 0x00, 0x01, 0x00,
+    0x01, 0x00, 0xee, 0x63, 0x01, 0x4b,
+];
+
+/// The EOF container ending a CRAM 2.1 file.
+const CRAM2_EOF: [u8; 30] = [
+    0x0b, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xe0, 0x45, 0x4f, 0x46, 0x00, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x06, 0x06, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00,
+];
+
+#[derive(Debug, Clone, Copy)]
+struct CheckOptions {
+    /// Files without @SQ lines pass, as for unaligned reads
+    allow_unmapped: bool,
+    /// Read the whole file, not just its header and end
+    full_decode: bool,
+    /// Check records against the declared sort order, which implies a full decode
+    check_order: bool,
+}
+
+fn ends_with(file: &mut fs::File, end: &[u8]) -> io::Result<bool> {
+    if file.metadata()?.len() < end.len() as u64 {
+        return Ok(false);
+    }
+    let mut buf = vec![0; end.len()];
+    file.seek(SeekFrom::End(-(end.len() as i64)))?;
+    file.read_exact(&mut buf)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(buf == end)
+}
+
+fn io_error(e: io::Error) -> String {
+    CommandError::from(e).to_string()
+}
+
+/// Reads `len` bytes, `None` if the stream ends first.
+fn read_bytes(r: &mut impl Read, len: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    Ok((bytes.len() as u64 == len).then_some(bytes))
+}
+
+/// Reads the header of a BAM stream: magic, SAM header text and the reference list, returning
+/// the header and the number of references.
+fn read_bam_header(r: &mut impl Read) -> Result<(Header, u32), String> {
+    fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        r.read_exact(&mut buf).map(|()| u32::from_le_bytes(buf))
+    }
+    let truncated = |_| "truncated BAM header".to_owned();
+    let text_len = read_u32(r).map_err(truncated)?;
+    // Lengths come from the file, so nothing is allocated before the bytes actually arrive
+    let text = read_bytes(r, u64::from(text_len))
+        .map_err(io_error)?
+        .ok_or("truncated BAM header")?;
+    let text = String::from_utf8_lossy(&text);
+    // The text may be padded with NULs
+    let header: Header = text
+        .trim_end_matches('\0')
+        .parse()
+        .map_err(|e| CommandError::Parse(e).to_string())?;
+    let ref_count = read_u32(r).map_err(truncated)?;
+    for _ in 0..ref_count {
+        let name_len = read_u32(r).map_err(truncated)?;
+        let skipped = io::copy(
+            &mut r.by_ref().take(u64::from(name_len) + 4),
+            &mut io::sink(),
+        )
+        .map_err(io_error)?;
+        if skipped < u64::from(name_len) + 4 {
+            return Err("truncated BAM header".into());
+        }
+    }
+    Ok((header, ref_count))
+}
+
+/// Reads an ITF8 integer, as in CRAM container and block headers.
+fn read_itf8(r: &mut impl Read) -> io::Result<i32> {
+    let mut byte = [0; 1];
+    r.read_exact(&mut byte)?;
+    let first = byte[0];
+    // The leading ones of the first byte count the bytes that follow
+    let n = first.leading_ones();
+    if n >= 4 {
+        let mut rest = [0; 4];
+        r.read_exact(&mut rest)?;
+        let value = u32::from(first & 0x0f) << 28
+            | u32::from(rest[0]) << 20
+            | u32::from(rest[1]) << 12
+            | u32::from(rest[2]) << 4
+            | u32::from(rest[3] & 0x0f);
+        return Ok(value as i32);
+    }
+    let mut value = u32::from(first) & (0xff >> (n + 1));
+    for _ in 0..n {
+        r.read_exact(&mut byte)?;
+        value = value << 8 | u32::from(byte[0]);
+    }
+    Ok(value as i32)
+}
+
+/// Reads an LTF8 integer, as in CRAM container headers.
+fn read_ltf8(r: &mut impl Read) -> io::Result<i64> {
+    let mut byte = [0; 1];
+    r.read_exact(&mut byte)?;
+    let n = byte[0].leading_ones();
+    let mut value = u64::from(byte[0]) & (0xff >> (n + 1));
+    for _ in 0..n {
+        r.read_exact(&mut byte)?;
+        value = value << 8 | u64::from(byte[0]);
+    }
+    Ok(value as i64)
+}
+
+/// Reads the SAM header from the first container of a CRAM stream, just after the file
+/// definition. CRAM 3 adds CRC32s to the container and block headers of CRAM 2.1.
+fn read_cram_header(r: &mut impl Read, major_version: u8) -> Result<Header, String> {
+    let truncated = |_| "truncated CRAM header".to_owned();
+    let size = |n: i32| u64::try_from(n).map_err(|_| "bad CRAM header".to_owned());
+    let has_crc = major_version >= 3;
+
+    // Container: length, reference, start, span, record count, record counter, bases, block
+    // count and landmarks
+    io::copy(&mut r.by_ref().take(4), &mut io::sink()).map_err(truncated)?;
+    for _ in 0..4 {
+        read_itf8(r).map_err(truncated)?;
+    }
+    read_ltf8(r).map_err(truncated)?;
+    read_ltf8(r).map_err(truncated)?;
+    read_itf8(r).map_err(truncated)?;
+    for _ in 0..size(read_itf8(r).map_err(truncated)?)? {
+        read_itf8(r).map_err(truncated)?;
+    }
+    if has_crc {
+        read_bytes(r, 4)
+            .map_err(io_error)?
+            .ok_or("truncated CRAM header")?;
+    }
+
+    // The first block holds the header text, raw or gzipped
+    let mut method = [0; 2];
+    r.read_exact(&mut method).map_err(truncated)?;
+    read_itf8(r).map_err(truncated)?;
+    let compressed_size = size(read_itf8(r).map_err(truncated)?)?;
+    let raw_size = size(read_itf8(r).map_err(truncated)?)?;
+    let data = read_bytes(r, compressed_size)
+        .map_err(io_error)?
+        .ok_or("truncated CRAM header")?;
+    let data = match method[0] {
+        0 => data,
+        1 => read_bytes(&mut GzDecoder::new(&data[..]), raw_size)
+            .map_err(io_error)?
+            .ok_or("truncated CRAM header")?,
+        _ => return Err("unsupported CRAM header compression".into()),
+    };
+    let Some((text_len, text)) = data.split_first_chunk::<4>() else {
+        return Err("truncated CRAM header".into());
+    };
+    let text_len = u32::from_le_bytes(*text_len) as usize;
+    let text = text.get(..text_len).ok_or("truncated CRAM header")?;
+    String::from_utf8_lossy(text)
+        .trim_end_matches('\0')
+        .parse()
+        .map_err(|e| CommandError::Parse(e).to_string())
+}
+
+/// Checks the header and optionally the records of a SAM stream.
+fn check_sam(reader: impl BufRead, options: CheckOptions) -> Result<(), String> {
+    let reader = Reader::new(reader).map_err(|e| CommandError::Parse(e).to_string())?;
+    if !options.allow_unmapped && reader.header().reference_seqs().next().is_none() {
+        return Err("no @SQ lines".into());
+    }
+    if !options.check_order {
+        if options.full_decode {
+            for record in reader {
+                record.map_err(|e| CommandError::Parse(e).to_string())?;
+            }
+        }
+        return Ok(());
+    }
+    let header = reader.header().clone();
+    let mut records = OrderChecked::new(reader, &header);
+    for record in records.by_ref() {
+        if let Err(e) = record {
+            return Err(match records.violation() {
+                Some(v) => format!(
+                    "record {} ({}) breaks {} order",
+                    v.record, v.query_name, v.rule
+                ),
+                None => CommandError::Parse(e).to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Formats whose records cannot be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binary {
+    Bam,
+    Cram,
+}
+
+/// The format of a BAM or CRAM file, `None` for anything else or a file that cannot be read.
+fn binary_format(path: &str) -> Option<Binary> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut magic = [0; 18];
+    let magic_len = file.read(&mut magic).ok()?;
+    let magic = &magic[..magic_len];
+    if magic.starts_with(b"CRAM") {
+        return Some(Binary::Cram);
+    }
+    if !bgzf::is_gzip(magic) {
+        return None;
+    }
+    file.seek(SeekFrom::Start(0)).ok()?;
+    let mut bam_magic = [0; 4];
+    MultiGzDecoder::new(BufReader::new(file))
+        .read_exact(&mut bam_magic)
+        .ok()?;
+    (&bam_magic == b"BAM\x01").then_some(Binary::Bam)
+}
+
+/// Checks one file, returning why it fails.
+fn check(path: &str, options: CheckOptions) -> Result<(), String> {
+    let mut file = fs::File::open(path).map_err(io_error)?;
+    let mut magic = [0; 18];
+    let magic_len = file.read(&mut magic).map_err(io_error)?;
+    let magic = &magic[..magic_len];
+    file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+
+    if let Some(version) = magic.strip_prefix(b"CRAM") {
+        // Only the header container is decoded, besides checking the EOF container
+        let (major_version, eof): (u8, &[u8]) = match version.first() {
+            Some(3) => (3, &CRAM3_EOF),
+            Some(2) => (2, &CRAM2_EOF),
+            _ => return Err("unsupported CRAM version".into()),
+        };
+        if !ends_with(&mut file, eof).map_err(io_error)? {
+            return Err("missing CRAM EOF container".into());
+        }
+        // The file definition is the magic, the version and a 20-byte file ID
+        file.seek(SeekFrom::Start(26)).map_err(io_error)?;
+        let header = read_cram_header(&mut BufReader::new(file), major_version)?;
+        if !options.allow_unmapped && header.reference_seqs().next().is_none() {
+            return Err("no @SQ lines".into());
+        }
+        return Ok(());
+    }
+
+    if bgzf::is_gzip(magic) {
+        // Plain gzip has no EOF marker to check, unlike BGZF
+        if bgzf::is_bgzf(magic) && !ends_with(&mut file, &bgzf::EOF_BLOCK).map_err(io_error)? {
+            return Err("missing BGZF EOF block".into());
+        }
+        let mut reader = BufReader::new(MultiGzDecoder::new(BufReader::new(file)));
+        let is_bam = reader.fill_buf().map_err(io_error)?.starts_with(b"BAM\x01");
+        if !is_bam {
+            return check_sam(reader, options);
+        }
+        reader.consume(4);
+        let (_, ref_count) = read_bam_header(&mut reader)?;
+        if !options.allow_unmapped && ref_count == 0 {
+            return Err("no reference sequences".into());
+        }
+        // Decompressing everything checks each block and its CRC, but not the records
+        if options.full_decode {
+            io::copy(&mut reader, &mut io::sink()).map_err(io_error)?;
+        }
+        return Ok(());
+    }
+
+    // SAM has no magic number, but text starts with a header line or a record
+    if magic.first().is_some_and(|&b| !b.is_ascii_graphic()) {
+        return Err("unknown file format".into());
+    }
+    check_sam(BufReader::new(file), options)
+}
+
+pub(super) fn run(args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut options = CheckOptions {
+        allow_unmapped: false,
+        full_decode: false,
+        check_order: false,
+    };
+    let mut quiet = false;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-u" => options.allow_unmapped = true,
+            "-d" => options.full_decode = true,
+            "-s" => options.check_order = true,
+            "-q" => quiet = true,
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(CommandError::Usage(
+            "samovar quickcheck [-u] [-d] [-s] [-q] <in.sam|in.bam|in.cram>...".into(),
+        ));
+    }
+
+    // Only SAM records are decoded: BAM blocks are decompressed but their records not checked
+    // for order, and CRAM containers are not decompressed at all
+    for path in &paths {
+        let unsupported = match binary_format(path) {
+            Some(Binary::Bam | Binary::Cram) if options.check_order => "-s",
+            Some(Binary::Cram) if options.full_decode => "-d",
+            _ => continue,
+        };
+        return Err(CommandError::Usage(format!(
+            "{unsupported} needs the records of {path} to be decoded, which is not supported"
+        )));
+    }
+
+    // Failing files are listed with the reason, one per line, and make the exit status non-zero
+    let mut out = create_output(None)?;
+    let mut failures = 0;
+    for path in &paths {
+        if let Err(reason) = check(path, options) {
+            if !quiet {
+                writeln!(out, "{path}\t{reason}")?;
+            }
+            failures += 1;
+        }
+    }
+    out.flush()?;
+    match failures {
+        0 => Ok(()),
+        failures => Err(CommandError::Invalid(failures)),
+    }
+}
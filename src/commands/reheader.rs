@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::{
+    alignment::{reader::Alignments, writer::write_alignment},
+    bgzf,
+    commands::{
+        CommandError, add_program_record, create_output, is_option,
+        renamecontigs::rename_alignment, unknown_option, value,
+    },
+    header::{Header, parser::ParseError, reader::read_header, writer::write_header},
+};
+
+/// A change to one header line, made after the header is replaced.
+enum Edit {
+    RemoveReferenceSeq(String),
+    RenameReferenceSeq(String, String),
+    RemoveReadGroup(String),
+    SetSample(String, String),
+    SetLibrary(String, String),
+    RemoveProgram(String),
+    AddComment(String),
+    RemoveComments,
+}
+
+/// Splits an `a=b` option value.
+fn pair(arg: &str, value: &str) -> Result<(String, String), CommandError> {
+    value
+        .split_once('=')
+        .map(|(a, b)| (a.to_owned(), b.to_owned()))
+        .ok_or_else(|| CommandError::Usage(format!("{arg} takes a value of the form a=b")))
+}
+
+fn apply(header: &mut Header, edit: Edit) -> Result<(), ParseError> {
+    match edit {
+        Edit::RemoveReferenceSeq(name) => {
+            header.remove_reference_seq(&name)?;
+        }
+        Edit::RenameReferenceSeq(name, new_name) => {
+            if header.reference_seq(&name).is_none() {
+                return Err(ParseError::UnknownKey);
+            }
+            header.rename_reference_seqs(|ref_seq| {
+                Some(if ref_seq.name() == name {
+                    new_name.clone()
+                } else {
+                    ref_seq.name().to_owned()
+                })
+            })?;
+        }
+        Edit::RemoveReadGroup(id) => {
+            header.remove_read_group(&id)?;
+        }
+        Edit::SetSample(id, sample) => header
+            .read_group_mut(&id)
+            .ok_or(ParseError::UnknownKey)?
+            .set_sample(Some(sample)),
+        Edit::SetLibrary(id, library) => header
+            .read_group_mut(&id)
+            .ok_or(ParseError::UnknownKey)?
+            .set_library(Some(library)),
+        Edit::RemoveProgram(id) => {
+            header.remove_program(&id)?;
+        }
+        Edit::AddComment(comment) => header.add_comment(comment),
+        Edit::RemoveComments => header.retain_comments(|_| false),
+    }
+    Ok(())
+}
+
+/// Runs `command` through the shell with the header as SAM text on its stdin, reading the new
+/// header from its stdout.
+fn filter_header(command: &str, header: &Header) -> Result<Header, CommandError> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut text = Vec::new();
+    write_header(&mut text, header)?;
+    let mut stdin = child.stdin.take().unwrap();
+    // Written from another thread so a command producing output early cannot deadlock
+    let writer = thread::spawn(move || stdin.write_all(&text));
+    let new_header = read_header(&mut BufReader::new(child.stdout.take().unwrap()));
+    let written = writer.join().unwrap();
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(CommandError::Usage(format!(
+            "header command failed with {status}"
+        )));
+    }
+    // The command may legitimately stop reading its input early
+    if let Err(e) = written
+        && e.kind() != io::ErrorKind::BrokenPipe
+    {
+        return Err(e.into());
+    }
+    Ok(new_header?)
+}
+
+/// Replaces or edits the header of a SAM file.
+///
+/// BAM and CRAM input is refused: their headers are not rewritten in place, and copying their
+/// records after a SAM header would corrupt them.
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut command = None;
+    let mut output = None;
+    let mut no_pg = false;
+    let mut edits = Vec::new();
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" => command = Some(value::<String>(&mut args, "-c")?),
+            "--remove-sq" => edits.push(Edit::RemoveReferenceSeq(value(&mut args, &arg)?)),
+            "--rename-sq" => {
+                let (name, new_name) = pair(&arg, &value::<String>(&mut args, &arg)?)?;
+                edits.push(Edit::RenameReferenceSeq(name, new_name));
+            }
+            "--remove-rg" => edits.push(Edit::RemoveReadGroup(value(&mut args, &arg)?)),
+            "--set-sample" => {
+                let (id, sample) = pair(&arg, &value::<String>(&mut args, &arg)?)?;
+                edits.push(Edit::SetSample(id, sample));
+            }
+            "--set-library" => {
+                let (id, library) = pair(&arg, &value::<String>(&mut args, &arg)?)?;
+                edits.push(Edit::SetLibrary(id, library));
+            }
+            "--remove-pg" => edits.push(Edit::RemoveProgram(value(&mut args, &arg)?)),
+            "--add-co" => edits.push(Edit::AddComment(value(&mut args, &arg)?)),
+            "--remove-co" => edits.push(Edit::RemoveComments),
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    // Edits alone change the existing header
+    let (header_path, path) = match (command.is_some(), paths.as_slice()) {
+        (true, [path]) => (None, path),
+        (false, [path]) if !edits.is_empty() => (None, path),
+        (false, [header, path]) => (Some(header), path),
+        _ => {
+            return Err(CommandError::Usage(
+                "samovar reheader [--remove-sq name] [--rename-sq old=new] [--remove-rg id] [--set-sample id=SM] [--set-library id=LB] [--remove-pg id] [--add-co text] [--remove-co] [--no-PG] [-o out] [<header.sam> | -c command] <in.sam>".into(),
+            ));
+        }
+    };
+
+    let mut reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(fs::File::open(path)?))
+    };
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(b"CRAM") {
+        return Err(CommandError::UnsupportedFormat("CRAM".into()));
+    } else if bgzf::is_gzip(magic) {
+        return Err(CommandError::UnsupportedFormat("BAM or compressed".into()));
+    }
+    let old_header = read_header(&mut reader)?;
+    let mut header = match (header_path, &command) {
+        (Some(header_path), _) => read_header(&mut BufReader::new(fs::File::open(header_path)?))?,
+        (None, Some(command)) => filter_header(command, &old_header)?,
+        (None, None) => old_header,
+    };
+    // New names of the references once the @SQ lines are edited, if they are; records on a
+    // removed one are errors, as in renamecontigs
+    let mut renames: Option<HashMap<String, String>> = None;
+    let unchanged = |header: &Header| {
+        header
+            .reference_seqs()
+            .map(|r| (r.name().to_owned(), r.name().to_owned()))
+            .collect()
+    };
+    for edit in edits {
+        match &edit {
+            Edit::RemoveReferenceSeq(name) => renames
+                .get_or_insert_with(|| unchanged(&header))
+                .retain(|_, new_name| new_name != name),
+            Edit::RenameReferenceSeq(name, new_name) => renames
+                .get_or_insert_with(|| unchanged(&header))
+                .values_mut()
+                .filter(|n| *n == name)
+                .for_each(|n| n.clone_from(new_name)),
+            _ => {}
+        }
+        apply(&mut header, edit)?;
+    }
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+    match renames {
+        Some(renames) => {
+            for alignment in Alignments::new(reader) {
+                let mut alignment = alignment?;
+                rename_alignment(&mut alignment, &renames)?;
+                write_alignment(&mut out, &alignment)?;
+            }
+        }
+        // Records are copied as they are, without being parsed
+        None => {
+            io::copy(&mut reader, &mut out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, Write},
+};
+
+use crate::{
+    alignment::{Alignment, tag::TagValue, writer::write_alignment},
+    commands::{
+        CommandError, add_program_record, create_output, is_option, open_sam, unknown_option, value,
+    },
+    header::writer::write_header,
+    sam::validation::Stringency,
+};
+
+/// Reads a mapping file of one `old new` pair per line, separated by whitespace. Blank lines and
+/// lines starting with `#` are skipped.
+fn read_map(path: &str) -> Result<HashMap<String, String>, CommandError> {
+    let mut map = HashMap::new();
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(old), Some(new), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(CommandError::Usage(format!("bad contig mapping: {line}")));
+        };
+        map.insert(old.to_owned(), new.to_owned());
+    }
+    Ok(map)
+}
+
+fn rename<'a>(renames: &'a HashMap<String, String>, name: &str) -> Result<&'a str, CommandError> {
+    renames
+        .get(name)
+        .map(String::as_str)
+        .ok_or_else(|| CommandError::UnmappedContig(name.into()))
+}
+
+/// Renames the references of an alignment, its mate and its SA tag.
+pub(super) fn rename_alignment(
+    alignment: &mut Alignment,
+    renames: &HashMap<String, String>,
+) -> Result<(), CommandError> {
+    if let Some(name) = alignment.reference_name() {
+        let name = rename(renames, name)?.to_owned();
+        alignment.set_position(name, alignment.position());
+    }
+    if !matches!(alignment.rnext(), "*" | "=") {
+        let rnext = rename(renames, alignment.rnext())?.to_owned();
+        alignment.set_mate(rnext, alignment.mate_position(), alignment.template_len());
+    }
+    // SA:Z:rname,pos,strand,CIGAR,mapQ,NM;...
+    if let Some(sa) = alignment
+        .tag(b"SA")
+        .and_then(|t| t.value.as_str())
+        .map(str::to_owned)
+    {
+        let mut renamed = String::new();
+        for part in sa.split_terminator(';') {
+            let (name, rest) = part.split_once(',').unwrap_or((part, ""));
+            renamed.push_str(rename(renames, name)?);
+            renamed.push(',');
+            renamed.push_str(rest);
+            renamed.push(';');
+        }
+        alignment.set_tag(*b"SA", TagValue::String(renamed));
+    }
+    Ok(())
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut map_path = None;
+    let mut use_alternate_names = false;
+    let mut output = None;
+    let mut no_pg = false;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-m" => map_path = Some(value::<String>(&mut args, "-m")?),
+            "-a" => use_alternate_names = true,
+            "--no-PG" => no_pg = true,
+            "-o" => output = Some(value::<String>(&mut args, "-o")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let (Some(path), true) = (path, map_path.is_some() || use_alternate_names) else {
+        return Err(CommandError::Usage(
+            "samovar renamecontigs (-m map.txt | -a | -m map.txt -a) [--no-PG] [-o out] <in.sam>"
+                .into(),
+        ));
+    };
+    let map = match &map_path {
+        Some(map_path) => read_map(map_path)?,
+        None => HashMap::new(),
+    };
+
+    let reader = open_sam(&path, stringency)?;
+    let mut header = reader.header().clone();
+    // The mapping file comes first, then with -a the first AN alternate name. Contigs given no
+    // new name either way are dropped from the header, and records on them are errors.
+    let mut renames = HashMap::new();
+    header.rename_reference_seqs(|ref_seq| {
+        let new_name = map.get(ref_seq.name()).cloned().or_else(|| {
+            use_alternate_names
+                .then(|| ref_seq.alternate_names().first().cloned())
+                .flatten()
+        })?;
+        renames.insert(ref_seq.name().to_owned(), new_name.clone());
+        Some(new_name)
+    })?;
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+
+    let mut out = create_output(output.as_deref())?;
+    write_header(&mut out, &header)?;
+    for alignment in reader {
+        let mut alignment = alignment?;
+        rename_alignment(&mut alignment, &renames)?;
+        write_alignment(&mut out, &alignment)?;
+    }
+    out.flush()?;
+    Ok(())
+}
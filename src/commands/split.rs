@@ -0,0 +1,273 @@
+use std::{collections::HashMap, io::Write, path::Path};
+
+use indexmap::IndexMap;
+
+use crate::{
+    alignment::{Alignment, writer::write_alignment},
+    commands::{
+        CommandError, add_program_record, create_output, is_option, open_sam, unknown_option, value,
+    },
+    header::{Header, ReadGroup, writer::write_header},
+    sam::validation::Stringency,
+};
+
+/// What the alignments are split by.
+#[derive(Debug, Clone, Copy)]
+enum SplitKey {
+    ReadGroup,
+    Sample,
+    Library,
+    Tag([u8; 2]),
+}
+
+impl SplitKey {
+    /// The key of a read group, `None` when splitting by tag.
+    fn of_read_group(self, read_group: &ReadGroup) -> Option<&str> {
+        match self {
+            Self::ReadGroup => Some(read_group.id()),
+            Self::Sample => read_group.sample(),
+            Self::Library => read_group.library(),
+            Self::Tag(_) => None,
+        }
+    }
+
+    fn of_alignment(self, alignment: &Alignment, header: &Header) -> Option<String> {
+        match self {
+            Self::Tag(name) => alignment.tag(&name).map(|t| t.value.to_string()),
+            _ => {
+                let id = alignment.tag(b"RG")?.value.as_str()?;
+                self.of_read_group(header.read_group(id)?)
+                    .map(str::to_owned)
+            }
+        }
+    }
+}
+
+/// Expands the placeholders of an output file name template.
+///
+/// `%*` is the input file name without its extension, `%!` the key value, `%#` the index of
+/// the key in order of appearance and `%%` a literal `%`.
+fn expand_template(template: &str, basename: &str, key: &str, index: usize) -> String {
+    let mut name = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            name.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('*') => name.push_str(basename),
+            // Key values could otherwise point into other directories
+            Some('!') => name.push_str(&key.replace('/', "_")),
+            Some('#') => name.push_str(&index.to_string()),
+            Some('%') => name.push('%'),
+            Some(other) => {
+                name.push('%');
+                name.push(other);
+            }
+            None => name.push('%'),
+        }
+    }
+    name
+}
+
+struct SplitOutputs<'a> {
+    header: &'a Header,
+    key: SplitKey,
+    template: String,
+    basename: String,
+    outputs: IndexMap<String, Box<dyn Write>>,
+    // Output path of each key value, reserved before its file is opened
+    paths: IndexMap<String, String>,
+    // What each reserved path is written for, as distinct keys can expand to the same path
+    owners: HashMap<String, String>,
+}
+
+impl SplitOutputs<'_> {
+    /// Output of the key value, created with its header on first use.
+    fn get(&mut self, value: &str) -> Result<&mut Box<dyn Write>, CommandError> {
+        if !self.outputs.contains_key(value) {
+            let path = self.reserve(value)?;
+            let mut out = create_output(Some(&path))?;
+
+            let mut header = self.header.clone();
+            let key = self.key;
+            if !matches!(key, SplitKey::Tag(_)) {
+                header.retain_read_groups(|rg| key.of_read_group(rg) == Some(value));
+            }
+            write_header(&mut out, &header)?;
+            self.outputs.insert(value.to_owned(), out);
+        }
+        Ok(self.outputs.get_mut(value).unwrap())
+    }
+
+    /// The output path of the key value, numbered in order of reservation.
+    fn reserve(&mut self, value: &str) -> Result<String, CommandError> {
+        if let Some(path) = self.paths.get(value) {
+            return Ok(path.clone());
+        }
+        let path = expand_template(&self.template, &self.basename, value, self.paths.len());
+        self.claim(path.clone(), format!("key {value}"))?;
+        self.paths.insert(value.to_owned(), path.clone());
+        Ok(path)
+    }
+
+    /// Reserves `path` for `owner`, failing if it is already reserved, so no file is truncated a
+    /// second time.
+    fn claim(&mut self, path: String, owner: String) -> Result<(), CommandError> {
+        match self.owners.get(&path) {
+            Some(first) => Err(CommandError::SharedOutput(path, first.clone(), owner)),
+            None => {
+                self.owners.insert(path, owner);
+                Ok(())
+            }
+        }
+    }
+}
+
+pub(super) fn run(
+    mut args: impl Iterator<Item = String>,
+    stringency: Stringency,
+) -> Result<(), CommandError> {
+    let mut key = SplitKey::ReadGroup;
+    let mut template = "%*_%#.sam".to_owned();
+    let mut unmatched = None;
+    let mut no_pg = false;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-s" => key = SplitKey::Sample,
+            "-l" => key = SplitKey::Library,
+            "-d" => {
+                let tag: String = value(&mut args, "-d")?;
+                key = match tag.as_bytes() {
+                    &[a, b] => SplitKey::Tag([a, b]),
+                    _ => return Err(CommandError::Usage(format!("bad tag {tag}"))),
+                };
+            }
+            "-f" => template = value(&mut args, "-f")?,
+            "--no-PG" => no_pg = true,
+            "-u" => unmatched = Some(value::<String>(&mut args, "-u")?),
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage(
+            "samovar split [-s | -l | -d tag] [-f template] [-u unmatched.sam] [--no-PG] <in.sam>"
+                .into(),
+        ));
+    };
+
+    let reader = open_sam(&path, stringency)?;
+    let mut header = reader.header().clone();
+    if !no_pg {
+        add_program_record(&mut header);
+    }
+    let basename = match path.as_str() {
+        "-" => "stdin".to_owned(),
+        path => Path::new(path)
+            .file_stem()
+            .map_or(path.into(), |s| s.to_string_lossy().into_owned()),
+    };
+    let mut outputs = SplitOutputs {
+        header: &header,
+        key,
+        template,
+        basename,
+        outputs: IndexMap::new(),
+        paths: IndexMap::new(),
+        owners: HashMap::new(),
+    };
+    // Paths known from the header are checked before any file is opened
+    if let Some(path) = &unmatched {
+        outputs.claim(path.clone(), "records without a key".into())?;
+    }
+    for read_group in header.read_groups() {
+        if let Some(value) = key.of_read_group(read_group) {
+            outputs.reserve(value)?;
+        }
+    }
+    // Every read group gets an output, even without alignments
+    for read_group in header.read_groups() {
+        if let Some(value) = key.of_read_group(read_group) {
+            outputs.get(value)?;
+        }
+    }
+    // Records without a key are dropped unless they have their own output
+    let mut unmatched = match unmatched {
+        Some(path) => {
+            let mut out = create_output(Some(&path))?;
+            write_header(&mut out, &header)?;
+            Some(out)
+        }
+        None => None,
+    };
+
+    for alignment in reader {
+        let alignment = alignment?;
+        match key.of_alignment(&alignment, &header) {
+            Some(value) => write_alignment(outputs.get(&value)?, &alignment)?,
+            None => {
+                if let Some(out) = &mut unmatched {
+                    write_alignment(out, &alignment)?;
+                }
+            }
+        }
+    }
+    for out in outputs.outputs.values_mut().chain(unmatched.as_mut()) {
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_placeholders() {
+        assert_eq!(
+            expand_template("%*_%!_%#.sam", "in", "rg1", 0),
+            "in_rg1_0.sam"
+        );
+        assert_eq!(expand_template("%!/%!", "in", "a", 3), "a/a");
+    }
+
+    #[test]
+    fn keeps_key_values_in_the_directory() {
+        assert_eq!(
+            expand_template("out/%!.sam", "in", "../x/y", 0),
+            "out/.._x_y.sam"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_and_escaped_percents() {
+        assert_eq!(expand_template("100%%_%q_%", "in", "k", 0), "100%_%q_%");
+        assert_eq!(expand_template("", "in", "k", 0), "");
+    }
+
+    #[test]
+    fn refuses_keys_expanding_to_the_same_path() {
+        let header = Header::default();
+        let mut outputs = SplitOutputs {
+            header: &header,
+            key: SplitKey::Tag(*b"BC"),
+            template: "%!.sam".into(),
+            basename: "in".into(),
+            outputs: IndexMap::new(),
+            paths: IndexMap::new(),
+            owners: HashMap::new(),
+        };
+        assert_eq!(outputs.reserve("a/b").unwrap(), "a_b.sam");
+        assert_eq!(outputs.reserve("a/b").unwrap(), "a_b.sam");
+        assert!(matches!(
+            outputs.reserve("a_b"),
+            Err(CommandError::SharedOutput(path, first, second))
+                if path == "a_b.sam" && first == "key a/b" && second == "key a_b"
+        ));
+    }
+}
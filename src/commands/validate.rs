@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use indexmap::IndexMap;
+
+use crate::{
+    commands::{CommandError, create_output, is_option, open_sam, unknown_option},
+    header::provenance::ProgramGraph,
+    sam::validation::{Stringency, validate},
+};
+
+/// Occurrences of a kind of problem: how many, and where the first one was.
+struct Occurrences {
+    count: u64,
+    first: String,
+}
+
+#[derive(Default)]
+struct Summary(IndexMap<&'static str, Occurrences>);
+
+impl Summary {
+    fn add(&mut self, name: &'static str, location: impl FnOnce() -> String) {
+        self.0
+            .entry(name)
+            .and_modify(|o| o.count += 1)
+            .or_insert_with(|| Occurrences {
+                count: 1,
+                first: location(),
+            });
+    }
+
+    fn total(&self) -> u64 {
+        self.0.values().map(|o| o.count).sum()
+    }
+}
+
+pub(super) fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut path = None;
+
+    for arg in args.by_ref() {
+        match arg.as_str() {
+            _ if is_option(&arg) => return Err(unknown_option(&arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(unknown_option(&arg)),
+        }
+    }
+    let Some(path) = path else {
+        return Err(CommandError::Usage("samovar validate <in.sam>".into()));
+    };
+
+    // All problems are counted here instead of stopping at the first, so the global stringency
+    // does not apply
+    let reader = open_sam(&path, Stringency::Silent)?;
+    let header = reader.header().clone();
+    let mut summary = Summary::default();
+    if let Err(e) = ProgramGraph::new(&header) {
+        summary.add("INVALID_PROGRAM_CHAIN", || format!("header: {e}"));
+    }
+    for e in header.read_group_problems() {
+        summary.add("INVALID_READ_GROUP", || format!("header: {e}"));
+    }
+
+    for (i, alignment) in reader.enumerate() {
+        let record = i + 1;
+        let alignment = match alignment {
+            Ok(alignment) => alignment,
+            Err(e) => {
+                summary.add("PARSE_ERROR", || e.to_string());
+                continue;
+            }
+        };
+        for problem in validate(&alignment, &header) {
+            summary.add(problem.name(), || {
+                format!("record {record} ({}): {problem}", alignment.query_name())
+            });
+        }
+    }
+
+    // One line per kind of problem, as ValidateSamFile's summary mode
+    let mut out = create_output(None)?;
+    match summary.total() {
+        0 => writeln!(out, "No errors found")?,
+        _ => {
+            writeln!(out, "ERROR\tCOUNT\tFIRST")?;
+            for (name, occurrences) in &summary.0 {
+                writeln!(out, "{name}\t{}\t{}", occurrences.count, occurrences.first)?;
+            }
+        }
+    }
+    out.flush()?;
+    match summary.total() {
+        0 => Ok(()),
+        total => Err(CommandError::Invalid(total)),
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::alignment::{Alignment, cigar::CigarOpKind};
+
+/// Unmapped, secondary, QC-failed and duplicate alignments don't count towards depth by default.
+pub(crate) const DEFAULT_EXCLUDE_FLAGS: u16 = 0x4 | 0x100 | 0x200 | 0x400;
+
+/// Decides which alignments and which of their bases contribute to depth.
+#[derive(Debug, Clone)]
+pub(crate) struct DepthOptions {
+    pub(crate) min_map_quality: u8,
+    pub(crate) min_base_quality: u8,
+    /// Alignments must have all of these flags set
+    pub(crate) include_flags: u16,
+    /// Alignments must have none of these flags set
+    pub(crate) exclude_flags: u16,
+    /// Count deletions (but not reference skips) as covering the reference
+    pub(crate) count_deletions: bool,
+    pub(crate) min_read_len: u32,
+    /// Count positions covered by both mates of an overlapping pair only once
+    pub(crate) remove_overlaps: bool,
+}
+
+impl Default for DepthOptions {
+    fn default() -> Self {
+        Self {
+            min_map_quality: 0,
+            min_base_quality: 0,
+            include_flags: 0,
+            exclude_flags: DEFAULT_EXCLUDE_FLAGS,
+            count_deletions: false,
+            min_read_len: 0,
+            remove_overlaps: false,
+        }
+    }
+}
+
+impl DepthOptions {
+    pub(crate) fn accepts(&self, alignment: &Alignment) -> bool {
+        let flag = alignment.flag().bits();
+        flag & self.include_flags == self.include_flags
+            && flag & self.exclude_flags == 0
+            && !alignment.flag().is_unmapped()
+            && alignment.position().is_some()
+            // An unavailable MAPQ passes, as 255 does in samtools
+            && alignment
+                .mapping_quality()
+                .is_none_or(|q| q >= self.min_map_quality)
+            && alignment.cigar().query_len() >= self.min_read_len
+    }
+}
+
+/// Bases counted by [`DepthCounter::add`].
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Counted {
+    /// Aligned bases passing the quality threshold
+    pub(crate) bases: u64,
+    /// Sum of the qualities of `bases`, where qualities are available
+    pub(crate) quality_sum: u64,
+    pub(crate) deletions: u64,
+}
+
+/// Accumulates per-position depth over a single reference for one or more inputs.
+///
+/// Alignments must be added in coordinate order; positions before the start of the last added
+/// alignment are complete and can be taken with [`DepthCounter::drain_until`].
+#[derive(Debug)]
+pub(crate) struct DepthCounter {
+    inputs: usize,
+    // 1-based reference position of `counts[0]`
+    start: u32,
+    counts: VecDeque<Vec<u32>>,
+    // Positions counted for alignments whose overlapping mate is yet to come, by input and name
+    pending_mates: HashMap<(usize, String), Vec<u32>>,
+}
+
+impl DepthCounter {
+    pub(crate) fn new(inputs: usize) -> Self {
+        Self {
+            inputs,
+            start: 1,
+            counts: VecDeque::new(),
+            pending_mates: HashMap::new(),
+        }
+    }
+
+    /// Drops any pending counts and starts over at the beginning of a new reference.
+    pub(crate) fn reset(&mut self) {
+        self.start = 1;
+        self.counts.clear();
+        self.pending_mates.clear();
+    }
+
+    /// Smallest position which can still receive counts.
+    pub(crate) fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        input: usize,
+        alignment: &Alignment,
+        options: &DepthOptions,
+    ) -> Counted {
+        let mut counted = Counted::default();
+        let Some(start) = alignment.position() else {
+            return counted;
+        };
+        let quality = alignment.quality().map_or(&[][..], |q| q.scores());
+
+        // Positions already counted for the mate, and whether the mate will need ours
+        let mut mate_counted = Vec::new();
+        let mut pending = None;
+        if options.remove_overlaps && alignment.flag().has_multiple_segments() {
+            let key = (input, alignment.query_name().to_owned());
+            match self.pending_mates.remove(&key) {
+                Some(positions) => mate_counted = positions,
+                None if mates_overlap(alignment) => pending = Some((key, Vec::new())),
+                None => {}
+            }
+        }
+
+        for aligned in alignment.cigar().aligned_positions(start.get()) {
+            let Some(ref_pos) = aligned.ref_pos else {
+                continue;
+            };
+            match aligned.kind {
+                CigarOpKind::Match | CigarOpKind::SequenceMatch | CigarOpKind::SequenceMismatch => {
+                    let q = aligned
+                        .query_pos
+                        .and_then(|i| quality.get(i as usize))
+                        .copied();
+                    if q.is_some_and(|q| q < options.min_base_quality) {
+                        continue;
+                    }
+                    counted.bases += 1;
+                    counted.quality_sum += u64::from(q.unwrap_or(0));
+                }
+                CigarOpKind::Deletion if options.count_deletions => counted.deletions += 1,
+                _ => continue,
+            }
+            if mate_counted.binary_search(&ref_pos).is_ok() {
+                continue;
+            }
+            if let Some((_, positions)) = &mut pending {
+                positions.push(ref_pos);
+            }
+            self.increment(input, ref_pos);
+        }
+        if let Some((key, positions)) = pending {
+            self.pending_mates.insert(key, positions);
+        }
+        counted
+    }
+
+    fn increment(&mut self, input: usize, ref_pos: u32) {
+        if ref_pos < self.start {
+            return;
+        }
+        let i = (ref_pos - self.start) as usize;
+        if i >= self.counts.len() {
+            self.counts.resize(i + 1, vec![0; self.inputs]);
+        }
+        self.counts[i][input] += 1;
+    }
+
+    /// Removes and returns the counts of all positions before `pos`.
+    ///
+    /// Positions without any counts in between are not returned.
+    pub(crate) fn drain_until(&mut self, pos: u32) -> Vec<(u32, Vec<u32>)> {
+        let mut drained = Vec::new();
+        while self.start < pos {
+            match self.counts.pop_front() {
+                Some(counts) => drained.push((self.start, counts)),
+                None => {
+                    self.start = pos;
+                    break;
+                }
+            }
+            self.start += 1;
+        }
+        // Mates which were filtered out or don't overlap after all can no longer show up
+        let start = self.start;
+        self.pending_mates
+            .retain(|_, positions| positions.last().is_some_and(|&last| last >= start));
+        drained
+    }
+}
+
+/// `true` if the mate of `alignment` comes later in coordinate order and starts within it.
+fn mates_overlap(alignment: &Alignment) -> bool {
+    !alignment.flag().next_is_unmapped()
+        && alignment.mate_on_same_reference()
+        && alignment
+            .position()
+            .zip(alignment.mate_position())
+            .is_some_and(|(pos, pnext)| {
+                pnext >= pos && pnext.get() < pos.get() + alignment.cigar().reference_len()
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alignment::parser::parse_alignment;
+
+    fn alignment(line: &str) -> Alignment {
+        parse_alignment(line.as_bytes()).unwrap()
+    }
+
+    fn depths(lines: &[&str], options: &DepthOptions) -> Vec<(u32, Vec<u32>)> {
+        let mut counter = DepthCounter::new(1);
+        for line in lines {
+            let alignment = alignment(line);
+            if options.accepts(&alignment) {
+                counter.add(0, &alignment, options);
+            }
+        }
+        counter.drain_until(u32::MAX)
+    }
+
+    #[test]
+    fn counts_deletions_but_not_skips_if_asked() {
+        let deletion = "r1\t0\tchr1\t1\t60\t2M2D2M\t*\t0\t0\tACGT\tIIII";
+        let skip = "r2\t0\tchr1\t11\t60\t2M2N2M\t*\t0\t0\tACGT\tIIII";
+        let options = DepthOptions {
+            count_deletions: true,
+            ..DepthOptions::default()
+        };
+        let mut counter = DepthCounter::new(1);
+        let counted = counter.add(0, &alignment(deletion), &options);
+        assert_eq!((counted.bases, counted.deletions), (4, 2));
+        counter.add(0, &alignment(skip), &options);
+        let covered: Vec<_> = counter
+            .drain_until(u32::MAX)
+            .into_iter()
+            .filter(|(_, d)| d[0] > 0)
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(covered, [1, 2, 3, 4, 5, 6, 11, 12, 15, 16]);
+
+        let counted = DepthCounter::new(1).add(0, &alignment(deletion), &DepthOptions::default());
+        assert_eq!((counted.bases, counted.deletions), (4, 0));
+        let depths = depths(&[deletion], &DepthOptions::default());
+        let depths: Vec<_> = depths.iter().map(|(_, d)| d[0]).collect();
+        assert_eq!(depths, [1, 1, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn skips_bases_below_the_base_quality() {
+        let options = DepthOptions {
+            min_base_quality: 20,
+            ..DepthOptions::default()
+        };
+        let mut counter = DepthCounter::new(1);
+        let counted = counter.add(
+            0,
+            &alignment("r1\t0\tchr1\t1\t60\t4M\t*\t0\t0\tACGT\tI+I5"),
+            &options,
+        );
+        assert_eq!((counted.bases, counted.quality_sum), (3, 40 + 40 + 20));
+        let depths: Vec<_> = counter
+            .drain_until(u32::MAX)
+            .iter()
+            .map(|(_, d)| d[0])
+            .collect();
+        assert_eq!(depths, [1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn filters_by_flags() {
+        let read = |flag: u16| alignment(&format!("r1\t{flag}\tchr1\t1\t60\t4M\t*\t0\t0\tACGT\t*"));
+        let options = DepthOptions::default();
+        assert!(options.accepts(&read(0)));
+        assert!(options.accepts(&read(0x800)));
+        for flag in [0x100, 0x200, 0x400] {
+            assert!(!options.accepts(&read(flag)));
+        }
+
+        let options = DepthOptions {
+            include_flags: 0x1 | 0x40,
+            exclude_flags: 0x800,
+            ..DepthOptions::default()
+        };
+        assert!(options.accepts(&read(0x1 | 0x40 | 0x400)));
+        assert!(!options.accepts(&read(0x1)));
+        assert!(!options.accepts(&read(0x1 | 0x40 | 0x800)));
+
+        // Unmapped reads never count, even when not excluded
+        let options = DepthOptions {
+            exclude_flags: 0,
+            ..DepthOptions::default()
+        };
+        assert!(!options.accepts(&read(0x4)));
+    }
+
+    #[test]
+    fn filters_by_mapping_quality_and_length() {
+        let read = |mapq: u8, cigar: &str| {
+            alignment(&format!("r1\t0\tchr1\t1\t{mapq}\t{cigar}\t*\t0\t0\t*\t*"))
+        };
+        let options = DepthOptions {
+            min_map_quality: 20,
+            min_read_len: 4,
+            ..DepthOptions::default()
+        };
+        assert!(options.accepts(&read(20, "4M")));
+        assert!(!options.accepts(&read(19, "4M")));
+        assert!(options.accepts(&read(255, "4M")));
+        // Soft clips count towards the read length, deletions don't
+        assert!(options.accepts(&read(60, "1S3M")));
+        assert!(!options.accepts(&read(60, "3M2D")));
+    }
+
+    // Mates overlapping at positions 3 and 4, disagreeing on the base at 3
+    const PAIR: [&str; 2] = [
+        "r1\t99\tchr1\t1\t60\t4M\t=\t3\t6\tACGT\tIIII",
+        "r1\t147\tchr1\t3\t60\t4M\t=\t1\t-6\tTTGG\t5555",
+    ];
+
+    #[test]
+    fn counts_overlapping_mates_twice_by_default() {
+        let depths = depths(&PAIR, &DepthOptions::default());
+        let depths: Vec<_> = depths.iter().map(|(_, d)| d[0]).collect();
+        assert_eq!(depths, [1, 1, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn counts_overlapping_mates_once() {
+        let options = DepthOptions {
+            remove_overlaps: true,
+            ..DepthOptions::default()
+        };
+        let depths = depths(&PAIR, &options);
+        assert_eq!(depths.first().map(|(pos, _)| *pos), Some(1));
+        let depths: Vec<_> = depths.iter().map(|(_, d)| d[0]).collect();
+        assert_eq!(depths, [1, 1, 1, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,134 @@
+pub mod index;
+
+use std::{
+    fs,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+
+use crate::{bgzf, fasta::index::FaiRecord, header::parser::ParseError, region::Region};
+
+/// Path of the index `ext` (e.g. `fai`) belonging to the FASTA file at `path`.
+pub(crate) fn index_path(path: &Path, ext: &str) -> PathBuf {
+    let mut index = path.as_os_str().to_owned();
+    index.push(".");
+    index.push(ext);
+    index.into()
+}
+
+enum Source<R> {
+    Plain(R),
+    Bgzf(bgzf::Reader<R>),
+}
+
+/// Random access to the sequences of a plain or BGZF-compressed FASTA file.
+pub(crate) struct IndexedReader<R> {
+    source: Source<R>,
+    index: IndexMap<String, FaiRecord>,
+}
+
+/// Builds the `.fai` index of a FASTA file, along with the `.gzi` index if it is BGZF-compressed.
+pub(crate) fn build_index(
+    path: impl AsRef<Path>,
+) -> Result<(Vec<FaiRecord>, Option<bgzf::BlockIndex>), ParseError> {
+    let mut file = open_file(path.as_ref())?;
+    if !is_compressed(&mut file)? {
+        return Ok((index::build(&mut file)?, None));
+    }
+    let blocks = bgzf::index_blocks(&mut file)?;
+    file.seek(SeekFrom::Start(0))
+        .map_err(|_| ParseError::IOError)?;
+    let mut reader = BufReader::new(bgzf::Reader::new(file, blocks.clone()));
+    Ok((index::build(&mut reader)?, Some(blocks)))
+}
+
+fn open_file(path: &Path) -> Result<BufReader<fs::File>, ParseError> {
+    fs::File::open(path)
+        .map(BufReader::new)
+        .map_err(|_| ParseError::IOError)
+}
+
+fn is_compressed(file: &mut BufReader<fs::File>) -> Result<bool, ParseError> {
+    let mut magic = [0; 2];
+    let compressed = file.read_exact(&mut magic).is_ok() && bgzf::is_gzip(&magic);
+    file.seek(SeekFrom::Start(0))
+        .map_err(|_| ParseError::IOError)?;
+    Ok(compressed)
+}
+
+impl IndexedReader<BufReader<fs::File>> {
+    /// Opens a FASTA file along with its `.fai` (and for BGZF files `.gzi`) index.
+    ///
+    /// Missing indexes are built in memory, which requires reading the whole file.
+    pub(crate) fn open(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        let path = path.as_ref();
+        let mut file = open_file(path)?;
+        let compressed = is_compressed(&mut file)?;
+
+        let fai = open_file(&index_path(path, "fai")).ok();
+        let gzi = open_file(&index_path(path, "gzi")).ok();
+        let (records, blocks) = match (fai, gzi) {
+            (Some(fai), _) if !compressed => (index::read(fai)?, None),
+            (Some(fai), Some(mut gzi)) => (index::read(fai)?, Some(bgzf::read_gzi(&mut gzi)?)),
+            _ => build_index(path)?,
+        };
+
+        let source = match blocks {
+            Some(blocks) => Source::Bgzf(bgzf::Reader::new(file, blocks)),
+            None => Source::Plain(file),
+        };
+        Ok(Self::new(source, records))
+    }
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    fn new(source: Source<R>, records: Vec<FaiRecord>) -> Self {
+        let index = records.into_iter().map(|r| (r.name.clone(), r)).collect();
+        Self { source, index }
+    }
+
+    /// Length of the sequence `name`, if it is in the index.
+    pub(crate) fn length(&self, name: &str) -> Option<u64> {
+        self.index.get(name).map(|r| r.length)
+    }
+
+    /// Bases of `region`, truncated to the end of the sequence.
+    pub(crate) fn fetch(&mut self, region: &Region) -> Result<Vec<u8>, ParseError> {
+        let record = self
+            .index
+            .get(&region.name)
+            .ok_or(ParseError::UnknownReference)?;
+        let start = u64::from(region.start) - 1;
+        let end = region
+            .end
+            .map_or(record.length, |end| u64::from(end).min(record.length));
+        if start >= end || record.line_bases == 0 {
+            return Ok(Vec::new());
+        }
+
+        let first = record.offset_of(start);
+        let last = record.offset_of(end - 1);
+        seek(&mut self.source, first)?;
+        let mut raw = vec![0; (last - first + 1) as usize];
+        match &mut self.source {
+            Source::Plain(r) => r.read_exact(&mut raw),
+            Source::Bgzf(r) => r.read_exact(&mut raw),
+        }
+        .map_err(|_| ParseError::IOError)?;
+
+        raw.retain(|&b| b != b'\n' && b != b'\r');
+        Ok(raw)
+    }
+}
+
+fn seek<R: Read + Seek>(source: &mut Source<R>, offset: u64) -> Result<(), ParseError> {
+    match source {
+        Source::Plain(r) => r
+            .seek(SeekFrom::Start(offset))
+            .map(|_| ())
+            .map_err(|_| ParseError::IOError),
+        Source::Bgzf(r) => r.seek_uncompressed(offset),
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{BufRead, Write};
+
+use crate::header::parser::ParseError;
+
+/// A line of a `.fai` index, locating one sequence in a FASTA file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FaiRecord {
+    pub(crate) name: String,
+    pub(crate) length: u64,
+    /// Offset of the first base, in the uncompressed file
+    pub(crate) offset: u64,
+    pub(crate) line_bases: u64,
+    /// Bytes per line, including the line terminator
+    pub(crate) line_width: u64,
+}
+
+impl FaiRecord {
+    /// Offset of the 0-based position `pos` of this sequence.
+    pub(crate) fn offset_of(&self, pos: u64) -> u64 {
+        self.offset + pos / self.line_bases * self.line_width + pos % self.line_bases
+    }
+}
+
+/// Indexes an uncompressed FASTA stream.
+///
+/// All lines of a sequence but the last must have the same length.
+pub(crate) fn build(reader: &mut impl BufRead) -> Result<Vec<FaiRecord>, ParseError> {
+    let mut records = Vec::new();
+    let mut current: Option<FaiRecord> = None;
+    // Set once a line shorter than `line_bases` ends the sequence
+    let mut short_line = false;
+    let mut offset = 0;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let n = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|_| ParseError::IOError)? as u64;
+        if n == 0 {
+            break;
+        }
+        offset += n;
+
+        if let Some(name) = line.strip_prefix(b">") {
+            let name = str::from_utf8(name).map_err(|_| ParseError::InvalidUTF8)?;
+            let name = name
+                .split_whitespace()
+                .next()
+                .ok_or(ParseError::MissingValue)?;
+            records.extend(current.take());
+            current = Some(FaiRecord {
+                name: name.into(),
+                length: 0,
+                offset,
+                line_bases: 0,
+                line_width: 0,
+            });
+            short_line = false;
+            continue;
+        }
+
+        let Some(record) = &mut current else {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return Err(ParseError::MissingPrefix);
+        };
+        let bases = line.trim_ascii_end().len() as u64;
+        if bases == 0 {
+            short_line = true;
+            continue;
+        }
+        if short_line {
+            return Err(ParseError::BadLineLength);
+        }
+        if record.line_bases == 0 {
+            record.line_bases = bases;
+            record.line_width = n;
+        } else if bases > record.line_bases
+            || (bases == record.line_bases && n != record.line_width)
+        {
+            return Err(ParseError::BadLineLength);
+        }
+        short_line = bases < record.line_bases;
+        record.length += bases;
+    }
+    records.extend(current);
+    Ok(records)
+}
+
+pub(crate) fn read(reader: impl BufRead) -> Result<Vec<FaiRecord>, ParseError> {
+    reader
+        .lines()
+        .map(|line| {
+            let line = line.map_err(|_| ParseError::IOError)?;
+            let fields: Vec<_> = line.split('\t').collect();
+            let [name, length, offset, line_bases, line_width] = fields[..] else {
+                return Err(ParseError::BadIndex);
+            };
+            let number = |s: &str| s.parse().map_err(|_| ParseError::BadIndex);
+            Ok(FaiRecord {
+                name: name.into(),
+                length: number(length)?,
+                offset: number(offset)?,
+                line_bases: number(line_bases)?,
+                line_width: number(line_width)?,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn write(w: &mut impl Write, records: &[FaiRecord]) -> std::io::Result<()> {
+    for r in records {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}",
+            r.name, r.length, r.offset, r.line_bases, r.line_width
+        )?;
+    }
+    Ok(())
+}
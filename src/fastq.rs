@@ -0,0 +1,74 @@
+use std::io::BufRead;
+
+use crate::header::parser::ParseError;
+
+/// A single FASTQ record, with the header line split at the first whitespace.
+#[derive(Debug)]
+pub(crate) struct FastqRecord {
+    pub(crate) name: String,
+    /// Rest of the header line after the name, if any
+    pub(crate) comment: Option<String>,
+    pub(crate) sequence: String,
+    pub(crate) quality: String,
+}
+
+/// Reads four-line FASTQ records, skipping blank lines between them.
+pub(crate) struct Reader<R> {
+    inner: R,
+    line: String,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line: String::new(),
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<&str>, ParseError> {
+        self.line.clear();
+        match self.inner.read_line(&mut self.line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(self.line.trim_end_matches(['\n', '\r']))),
+            Err(_) => Err(ParseError::IOError),
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<FastqRecord>, ParseError> {
+        let header = loop {
+            match self.read_line()? {
+                None => return Ok(None),
+                Some("") => continue,
+                Some(line) => break line,
+            }
+        };
+        let header = header.strip_prefix('@').ok_or(ParseError::BadFastq)?;
+        let (name, comment) = match header.split_once([' ', '\t']) {
+            Some((name, comment)) => (name.into(), Some(comment.trim_start().into())),
+            None => (header.into(), None),
+        };
+        let sequence = self.read_line()?.ok_or(ParseError::BadFastq)?.to_owned();
+        if !self.read_line()?.is_some_and(|l| l.starts_with('+')) {
+            return Err(ParseError::BadFastq);
+        }
+        let quality = self.read_line()?.ok_or(ParseError::BadFastq)?.to_owned();
+        if quality.len() != sequence.len() {
+            return Err(ParseError::BadFastq);
+        }
+        Ok(Some(FastqRecord {
+            name,
+            comment,
+            sequence,
+            quality,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<FastqRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
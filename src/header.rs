@@ -1,19 +1,282 @@
 use std::{collections::HashMap, str::FromStr};
 
+use indexmap::IndexMap;
+
 use crate::header::parser::ParseError;
 
+pub mod dictionary;
 pub mod parser;
+pub mod provenance;
 pub mod reader;
+pub mod writer;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub(crate) struct Header {
     meta: Option<HeaderMeta>,
-    reference_seqs: HashMap<String, ReferenceSeq>,
-    read_groups: HashMap<String, ReadGroup>,
-    programs: HashMap<ProgramID, Program>,
+    // Kept in file order, which defines the reference indices used for sorting
+    reference_seqs: IndexMap<String, ReferenceSeq>,
+    read_groups: IndexMap<String, ReadGroup>,
+    programs: IndexMap<ProgramID, Program>,
     comments: Vec<String>,
 }
 
+impl Header {
+    pub(crate) fn set_meta(&mut self, meta: HeaderMeta) {
+        self.meta = Some(meta);
+    }
+
+    pub(crate) fn sort_order(&self) -> Option<&SortOrder> {
+        self.meta.as_ref()?.alignment_sort_order.as_ref()
+    }
+
+    pub(crate) fn grouping(&self) -> Option<&AlignmentGrouping> {
+        self.meta.as_ref()?.alignment_grouping.as_ref()
+    }
+
+    /// The SS sub-sort order, e.g. `queryname:natural` or `coordinate:MI`.
+    pub(crate) fn sub_sorting(&self) -> Option<&str> {
+        self.meta.as_ref()?.alignment_sub_sorting.as_deref()
+    }
+
+    /// Records how the alignments are ordered, keeping the version of an existing @HD line.
+    pub(crate) fn set_sorting(
+        &mut self,
+        sort_order: SortOrder,
+        grouping: Option<AlignmentGrouping>,
+    ) {
+        let meta = self.meta.get_or_insert_with(|| HeaderMeta::new(1, 6, None));
+        meta.alignment_sort_order = Some(sort_order);
+        meta.alignment_grouping = grouping;
+        meta.alignment_sub_sorting = None;
+    }
+
+    pub(crate) fn add_reference_seq(&mut self, ref_seq: ReferenceSeq) -> Result<(), ParseError> {
+        if self.reference_seqs.contains_key(&ref_seq.name) {
+            return Err(ParseError::DuplicateKey);
+        }
+        self.reference_seqs.insert(ref_seq.name.clone(), ref_seq);
+        Ok(())
+    }
+
+    pub(crate) fn add_read_group(&mut self, read_group: ReadGroup) -> Result<(), ParseError> {
+        if self.read_groups.contains_key(&read_group.id) {
+            return Err(ParseError::DuplicateKey);
+        }
+        self.read_groups.insert(read_group.id.clone(), read_group);
+        Ok(())
+    }
+
+    /// Adds the read group, or replaces the one with the same ID where it is.
+    pub(crate) fn set_read_group(&mut self, read_group: ReadGroup) -> Option<ReadGroup> {
+        self.read_groups.insert(read_group.id.clone(), read_group)
+    }
+
+    pub(crate) fn remove_read_group(&mut self, id: &str) -> Result<ReadGroup, ParseError> {
+        self.read_groups
+            .shift_remove(id)
+            .ok_or(ParseError::UnknownKey)
+    }
+
+    pub(crate) fn read_group_mut(&mut self, id: &str) -> Option<&mut ReadGroup> {
+        self.read_groups.get_mut(id)
+    }
+
+    pub(crate) fn read_groups(&self) -> impl Iterator<Item = &ReadGroup> {
+        self.read_groups.values()
+    }
+
+    /// Keeps only the @RG lines for which `keep` returns `true`.
+    pub(crate) fn retain_read_groups(&mut self, mut keep: impl FnMut(&ReadGroup) -> bool) {
+        self.read_groups.retain(|_, rg| keep(rg));
+    }
+
+    pub(crate) fn read_group(&self, id: &str) -> Option<&ReadGroup> {
+        self.read_groups.get(id)
+    }
+
+    /// Removes a @PG line, linking the programs that followed it to its own PP. A program named
+    /// by the PG of a read group cannot be removed.
+    pub(crate) fn remove_program(&mut self, id: &str) -> Result<Program, ParseError> {
+        let id = ProgramID(id.into());
+        if self
+            .read_groups
+            .values()
+            .any(|rg| rg.program.as_ref() == Some(&id))
+        {
+            return Err(ParseError::KeyInUse);
+        }
+        let removed = self
+            .programs
+            .shift_remove(&id)
+            .ok_or(ParseError::UnknownKey)?;
+        for program in self.programs.values_mut() {
+            if program.previous.as_ref() == Some(&removed.id) {
+                program.previous = removed.previous.clone();
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Adds a @PG line at the end of the program chain: its ID is suffixed with `.1`, `.2`, ...
+    /// when already taken, and its PP is the last program no other program follows.
+    pub(crate) fn append_program(&mut self, mut program: Program) {
+        let base = program.id.0.clone();
+        let mut suffix = 0;
+        while self.programs.contains_key(&program.id) {
+            suffix += 1;
+            program.id = ProgramID(format!("{base}.{suffix}"));
+        }
+        program.previous = self
+            .programs
+            .values()
+            .rev()
+            .find(|p| {
+                !self
+                    .programs
+                    .values()
+                    .any(|q| q.previous.as_ref() == Some(&p.id))
+            })
+            .map(|p| p.id.clone());
+        self.programs.insert(program.id.clone(), program);
+    }
+
+    pub(crate) fn program(&self, id: &str) -> Option<&Program> {
+        self.programs.get(&ProgramID(id.into()))
+    }
+
+    /// The @PG line named by the PG field of a read group.
+    pub(crate) fn read_group_program(&self, read_group: &ReadGroup) -> Option<&Program> {
+        self.programs.get(read_group.program.as_ref()?)
+    }
+
+    /// Strict checks of the @RG lines beyond their syntax: DT must be an ISO 8601 date, FO `*` or
+    /// IUPAC codes, PG must name a @PG line and KS must be read by the first flows of FO.
+    pub(crate) fn check_read_groups(&self) -> Result<(), ParseError> {
+        match self.read_group_problems().into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(()),
+        }
+    }
+
+    /// Every problem found by [`Header::check_read_groups`], in @RG order.
+    pub(crate) fn read_group_problems(&self) -> Vec<ParseError> {
+        let mut problems = Vec::new();
+        for read_group in self.read_groups.values() {
+            if let Err(e) = read_group.date() {
+                problems.push(e);
+            }
+            if let Some(flow_order) = &read_group.flow_order
+                && !parser::is_flow_order(flow_order)
+            {
+                problems.push(ParseError::BadFlowOrder {
+                    read_group: read_group.id.clone(),
+                    value: flow_order.clone(),
+                });
+            }
+            if let Some(program) = &read_group.program
+                && self.read_group_program(read_group).is_none()
+            {
+                problems.push(ParseError::DanglingProgram {
+                    read_group: read_group.id.clone(),
+                    value: program.0.clone(),
+                });
+            }
+            if let Some(key_sequence) = &read_group.key_sequence
+                && !read_group.key_sequence_fits_flows()
+            {
+                problems.push(ParseError::BadKeySequence {
+                    read_group: read_group.id.clone(),
+                    value: key_sequence.clone(),
+                });
+            }
+        }
+        problems
+    }
+
+    pub(crate) fn add_comment(&mut self, comment: String) {
+        self.comments.push(comment);
+    }
+
+    pub(crate) fn retain_comments(&mut self, keep: impl FnMut(&String) -> bool) {
+        self.comments.retain(keep);
+    }
+
+    /// Removes an @SQ line, shifting the reference indices of those after it. A sequence another
+    /// one names as its alternate locus (AH) cannot be removed.
+    pub(crate) fn remove_reference_seq(&mut self, name: &str) -> Result<ReferenceSeq, ParseError> {
+        let in_use = self.reference_seqs.values().any(|ref_seq| {
+            ref_seq.alternate_locus.as_deref().is_some_and(|locus| {
+                locus == name || locus.rsplit_once(':').is_some_and(|(n, _)| n == name)
+            })
+        });
+        if in_use {
+            return Err(ParseError::KeyInUse);
+        }
+        self.reference_seqs
+            .shift_remove(name)
+            .ok_or(ParseError::UnknownKey)
+    }
+
+    /// Renames all @SQ lines at once, dropping those `rename` gives no new name. A renamed
+    /// sequence keeps its old name as an AN alternate name, and AH alternate loci follow it. A
+    /// sequence named by the AH of one that is kept cannot be dropped.
+    pub(crate) fn rename_reference_seqs(
+        &mut self,
+        mut rename: impl FnMut(&ReferenceSeq) -> Option<String>,
+    ) -> Result<(), ParseError> {
+        let mut new_names = HashMap::new();
+        for ref_seq in self.reference_seqs.values() {
+            new_names.insert(ref_seq.name.clone(), rename(ref_seq));
+        }
+        let mut renamed = IndexMap::new();
+        // Built aside so the header is left as it was on error
+        for (name, ref_seq) in &self.reference_seqs {
+            let Some(new_name) = new_names[name].clone() else {
+                continue;
+            };
+            let mut ref_seq = ref_seq.clone();
+            // AH is `*` or a region `name:start-end` of another sequence
+            if let Some(locus) = &mut ref_seq.alternate_locus
+                && let Some((target, range)) = locus.rsplit_once(':')
+                && let Some(new_target) = new_names.get(target)
+            {
+                let new_target = new_target.as_ref().ok_or(ParseError::KeyInUse)?;
+                *locus = format!("{new_target}:{range}");
+            }
+            if new_name != *name {
+                let alternate_names = ref_seq.alternate_names.get_or_insert_default();
+                alternate_names.retain(|n| *n != new_name);
+                if !alternate_names.contains(name) {
+                    alternate_names.push(name.clone());
+                }
+                ref_seq.name = new_name.clone();
+            }
+            if renamed.insert(new_name, ref_seq).is_some() {
+                return Err(ParseError::DuplicateKey);
+            }
+        }
+        self.reference_seqs = renamed;
+        Ok(())
+    }
+
+    pub(crate) fn reference_seqs(&self) -> impl Iterator<Item = &ReferenceSeq> {
+        self.reference_seqs.values()
+    }
+
+    pub(crate) fn reference_seq(&self, name: &str) -> Option<&ReferenceSeq> {
+        self.reference_seqs.get(name)
+    }
+
+    pub(crate) fn reference_seq_at(&self, index: usize) -> Option<&ReferenceSeq> {
+        self.reference_seqs.get_index(index).map(|(_, r)| r)
+    }
+
+    /// Position of the @SQ line named `name` among all @SQ lines.
+    pub(crate) fn reference_seq_index(&self, name: &str) -> Option<usize> {
+        self.reference_seqs.get_index_of(name)
+    }
+}
+
 impl FromStr for Header {
     type Err = ParseError;
 
@@ -22,8 +285,8 @@ impl FromStr for Header {
     }
 }
 
-#[derive(Debug, Default)]
-struct HeaderMeta {
+#[derive(Debug, Default, Clone)]
+pub(crate) struct HeaderMeta {
     // VN
     format_version: Version,
     // SO
@@ -33,14 +296,24 @@ struct HeaderMeta {
     // SS
     alignment_sub_sorting: Option<String>,
 }
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct Version {
     major: usize,
     minor: usize,
 }
 
-#[derive(Debug, Default)]
-enum SortOrder {
+impl HeaderMeta {
+    pub(crate) fn new(major: usize, minor: usize, sort_order: Option<SortOrder>) -> Self {
+        Self {
+            format_version: Version { major, minor },
+            alignment_sort_order: sort_order,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) enum SortOrder {
     #[default]
     Unknown,
     Unsorted,
@@ -48,16 +321,16 @@ enum SortOrder {
     Coordinate,
 }
 
-#[derive(Debug, Default)]
-enum AlignmentGrouping {
+#[derive(Debug, Default, Clone)]
+pub(crate) enum AlignmentGrouping {
     #[default]
     None,
     Query,
     Reference,
 }
 
-#[derive(Debug, Default)]
-struct ReferenceSeq {
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ReferenceSeq {
     // SN
     name: String,
     // LN
@@ -80,14 +353,60 @@ struct ReferenceSeq {
     uri: Option<String>,
 }
 
-#[derive(Debug)]
+impl ReferenceSeq {
+    pub(crate) fn new(name: String, length: u64) -> Self {
+        Self {
+            name,
+            length,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_assembly_id(mut self, assembly_id: Option<String>) -> Self {
+        self.assembly_id = assembly_id;
+        self
+    }
+
+    pub(crate) fn with_checksum(mut self, checksum: Option<String>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub(crate) fn with_species(mut self, species: Option<String>) -> Self {
+        self.species = species;
+        self
+    }
+
+    pub(crate) fn with_uri(mut self, uri: Option<String>) -> Self {
+        self.uri = uri;
+        self
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn length(&self) -> u64 {
+        self.length
+    }
+
+    pub(crate) fn alternate_names(&self) -> &[String] {
+        self.alternate_names.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn checksum(&self) -> Option<&str> {
+        self.checksum.as_deref()
+    }
+}
+
+#[derive(Debug, Clone)]
 enum Topology {
     Linear,
     Circular,
 }
 
-#[derive(Debug, Default)]
-struct ReadGroup {
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ReadGroup {
     // ID
     id: String,
     // BC
@@ -96,16 +415,16 @@ struct ReadGroup {
     center: Option<String>,
     // DS
     description: Option<String>,
-    // DT
+    // DT, as written
     date: Option<String>,
-    // FO
+    // FO, as written
     flow_order: Option<String>,
     // KS
     key_sequence: Option<String>,
     // LB
     library: Option<String>,
-    // PG
-    programs: Option<String>,
+    // PG, the ID of a @PG line
+    program: Option<ProgramID>,
     // PI
     insert_size: Option<u32>,
     // PL
@@ -118,7 +437,118 @@ struct ReadGroup {
     sample: Option<String>,
 }
 
-#[derive(Debug)]
+impl ReadGroup {
+    pub(crate) fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(crate) fn barcode(&self) -> Option<&str> {
+        self.barcode.as_deref()
+    }
+
+    pub(crate) fn library(&self) -> Option<&str> {
+        self.library.as_deref()
+    }
+
+    pub(crate) fn sample(&self) -> Option<&str> {
+        self.sample.as_deref()
+    }
+
+    /// DT as an ISO 8601 date, an error naming the read group if it is malformed.
+    pub(crate) fn date(&self) -> Result<Option<DateTime>, ParseError> {
+        let Some(value) = &self.date else {
+            return Ok(None);
+        };
+        parser::parse_date_time(value)
+            .map(Some)
+            .ok_or_else(|| ParseError::BadDate {
+                read_group: self.id.clone(),
+                value: value.clone(),
+            })
+    }
+
+    /// Whether the key sequence is what the first flows of the flow order would read, which
+    /// holds trivially without either or with a `*` flow order.
+    pub(crate) fn key_sequence_fits_flows(&self) -> bool {
+        let (Some(key_sequence), Some(flow_order)) = (&self.key_sequence, &self.flow_order) else {
+            return true;
+        };
+        if flow_order == "*" {
+            return true;
+        }
+        let mut flows = flow_order.bytes();
+        let mut previous = None;
+        for base in key_sequence.bytes().map(|b| b.to_ascii_uppercase()) {
+            // A homopolymer is read by a single flow
+            if previous == Some(base) {
+                continue;
+            }
+            if !flows.any(|flow| iupac_matches(flow, base)) {
+                return false;
+            }
+            previous = Some(base);
+        }
+        true
+    }
+
+    pub(crate) fn set_library(&mut self, library: Option<String>) {
+        self.library = library;
+    }
+
+    pub(crate) fn set_sample(&mut self, sample: Option<String>) {
+        self.sample = sample;
+    }
+}
+
+impl FromStr for ReadGroup {
+    type Err = ParseError;
+
+    /// Parses the tab-separated fields of an @RG line, e.g. `ID:rg1\tSM:sample\tPL:ILLUMINA`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        parser::parse_read_group_fields(value)
+    }
+}
+
+/// Whether a nucleotide flow, given as an IUPAC code, can incorporate `base`.
+fn iupac_matches(flow: u8, base: u8) -> bool {
+    let bases: &[u8] = match flow {
+        b'A' | b'C' | b'G' | b'T' => return flow == base,
+        b'M' => b"AC",
+        b'R' => b"AG",
+        b'W' => b"AT",
+        b'S' => b"CG",
+        b'Y' => b"CT",
+        b'K' => b"GT",
+        b'V' => b"ACG",
+        b'H' => b"ACT",
+        b'D' => b"AGT",
+        b'B' => b"CGT",
+        b'N' => b"ACGT",
+        _ => b"",
+    };
+    bases.contains(&base)
+}
+
+/// A DT value: an ISO 8601 date, optionally with a time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DateTime {
+    pub(crate) year: u16,
+    pub(crate) month: u8,
+    pub(crate) day: u8,
+    pub(crate) time: Option<Time>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Time {
+    pub(crate) hour: u8,
+    pub(crate) minute: u8,
+    pub(crate) second: u8,
+    pub(crate) nanosecond: u32,
+    /// Offset from UTC in minutes, `None` for local time
+    pub(crate) utc_offset: Option<i16>,
+}
+
+#[derive(Debug, Clone)]
 enum Platform {
     Capillary,
     Dnbseq,
@@ -135,10 +565,10 @@ enum Platform {
 }
 
 #[derive(Debug, Default, Clone, PartialEq, PartialOrd, Eq, Hash)]
-struct ProgramID(String);
+pub(crate) struct ProgramID(String);
 
-#[derive(Debug, Default)]
-struct Program {
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Program {
     // ID
     id: ProgramID,
     // PN
@@ -152,3 +582,31 @@ struct Program {
     // VN
     version: Option<String>,
 }
+
+impl Program {
+    pub(crate) fn new(id: String) -> Self {
+        Self {
+            id: ProgramID(id),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub(crate) fn with_command_line(mut self, command_line: Option<String>) -> Self {
+        self.command_line = command_line;
+        self
+    }
+
+    pub(crate) fn with_version(mut self, version: Option<String>) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub(crate) fn id(&self) -> &str {
+        &self.id.0
+    }
+}
@@ -0,0 +1,222 @@
+use crate::header::{Header, ReferenceSeq};
+
+/// How the sequence dictionary of a header relates to that of another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Relation {
+    /// The same sequences in the same order
+    Identical,
+    /// The same sequences in another order
+    Reordered,
+    /// All sequences are in the other dictionary, which has more
+    Subset,
+    /// The other dictionary's sequences are all in this one, which has more
+    Superset,
+    /// Each dictionary has sequences the other lacks
+    Partial,
+    /// Some sequence only matches under another name, through AN or M5, so records must be
+    /// renamed before they can be merged
+    Renamed,
+    /// Some sequence differs in length or checksum between the dictionaries
+    Conflicting,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Difference<'a> {
+    OnlyInFirst(&'a ReferenceSeq),
+    OnlyInSecond(&'a ReferenceSeq),
+    /// The same sequence under another name, matched through AN or M5
+    Renamed(&'a ReferenceSeq, &'a ReferenceSeq),
+    /// The same sequence at another index
+    Moved(&'a ReferenceSeq, usize, usize),
+    Length(&'a ReferenceSeq, &'a ReferenceSeq),
+    Checksum(&'a ReferenceSeq, &'a ReferenceSeq),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Comparison<'a> {
+    pub(crate) relation: Relation,
+    pub(crate) differences: Vec<Difference<'a>>,
+}
+
+/// Names of a sequence: its SN and AN alternate names.
+fn names(seq: &ReferenceSeq) -> impl Iterator<Item = &str> {
+    std::iter::once(seq.name.as_str())
+        .chain(seq.alternate_names.iter().flatten().map(String::as_str))
+}
+
+/// Finds the index in `seqs` of the record matching `seq`, by exact name first, then by alias,
+/// then by checksum.
+fn find_match(seq: &ReferenceSeq, seqs: &[&ReferenceSeq], taken: &[bool]) -> Option<usize> {
+    let free = |i: &usize| !taken[*i];
+    (0..seqs.len())
+        .filter(free)
+        .find(|&i| seqs[i].name == seq.name)
+        .or_else(|| {
+            (0..seqs.len())
+                .filter(free)
+                .find(|&i| names(seq).any(|n| names(seqs[i]).any(|m| m == n)))
+        })
+        .or_else(|| {
+            let checksum = seq.checksum.as_ref()?;
+            (0..seqs.len())
+                .filter(free)
+                .find(|&i| seqs[i].checksum.as_ref() == Some(checksum))
+        })
+}
+
+/// Compares the @SQ lines of two headers, matching sequences by name, AN alternate names and M5
+/// checksum, and checking the lengths and checksums of matched sequences.
+pub(crate) fn compare<'a>(first: &'a Header, second: &'a Header) -> Comparison<'a> {
+    let first: Vec<&ReferenceSeq> = first.reference_seqs.values().collect();
+    let second: Vec<&ReferenceSeq> = second.reference_seqs.values().collect();
+    let mut differences = Vec::new();
+    let mut taken = vec![false; second.len()];
+    let mut matches = Vec::new();
+
+    for (i, seq) in first.iter().enumerate() {
+        let Some(j) = find_match(seq, &second, &taken) else {
+            differences.push(Difference::OnlyInFirst(seq));
+            continue;
+        };
+        taken[j] = true;
+        matches.push((i, j));
+        let other = second[j];
+        if seq.name != other.name {
+            differences.push(Difference::Renamed(seq, other));
+        }
+        if seq.length != other.length {
+            differences.push(Difference::Length(seq, other));
+        }
+        // Checksums can only be compared when both are known
+        if let (Some(a), Some(b)) = (&seq.checksum, &other.checksum)
+            && !a.eq_ignore_ascii_case(b)
+        {
+            differences.push(Difference::Checksum(seq, other));
+        }
+    }
+    for (j, seq) in second.iter().enumerate() {
+        if !taken[j] {
+            differences.push(Difference::OnlyInSecond(seq));
+        }
+    }
+    // Shared sequences are in order when their indices among the shared ones agree
+    let mut second_order: Vec<usize> = matches.iter().map(|&(_, j)| j).collect();
+    second_order.sort_unstable();
+    let in_order = matches
+        .iter()
+        .map(|&(_, j)| j)
+        .eq(second_order.iter().copied());
+    if !in_order {
+        for (&(i, j), &expected) in matches.iter().zip(&second_order) {
+            if j != expected {
+                differences.push(Difference::Moved(first[i], i, j));
+            }
+        }
+    }
+
+    let only_first = first.len() > matches.len();
+    let only_second = second.len() > matches.len();
+    let relation = if differences
+        .iter()
+        .any(|d| matches!(d, Difference::Length(..) | Difference::Checksum(..)))
+    {
+        Relation::Conflicting
+    } else if differences
+        .iter()
+        .any(|d| matches!(d, Difference::Renamed(..)))
+    {
+        Relation::Renamed
+    } else {
+        match (only_first, only_second) {
+            (false, false) if in_order => Relation::Identical,
+            (false, false) => Relation::Reordered,
+            (false, true) => Relation::Subset,
+            (true, false) => Relation::Superset,
+            (true, true) => Relation::Partial,
+        }
+    };
+    Comparison {
+        relation,
+        differences,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const M5_A: &str = "0123456789abcdef0123456789abcdef";
+    const M5_B: &str = "fedcba9876543210fedcba9876543210";
+
+    /// A header of @SQ lines, each given as its tab-separated fields.
+    fn header(seqs: &[&str]) -> Header {
+        let text: String = seqs.iter().map(|s| format!("@SQ\t{s}\n")).collect();
+        text.parse().unwrap()
+    }
+
+    fn relation(first: &[&str], second: &[&str]) -> Relation {
+        compare(&header(first), &header(second)).relation
+    }
+
+    #[test]
+    fn relates_dictionaries_by_their_sequences() {
+        let a = "SN:a\tLN:10";
+        let b = "SN:b\tLN:20";
+        let c = "SN:c\tLN:30";
+        assert_eq!(relation(&[a, b], &[a, b]), Relation::Identical);
+        assert_eq!(relation(&[], &[]), Relation::Identical);
+        assert_eq!(relation(&[a, b], &[b, a]), Relation::Reordered);
+        assert_eq!(relation(&[a], &[a, b]), Relation::Subset);
+        assert_eq!(relation(&[a, b], &[b]), Relation::Superset);
+        assert_eq!(relation(&[a, b], &[b, c]), Relation::Partial);
+    }
+
+    #[test]
+    fn keeps_order_around_missing_sequences() {
+        let a = "SN:a\tLN:10";
+        let b = "SN:b\tLN:20";
+        let c = "SN:c\tLN:30";
+        assert_eq!(relation(&[a, c], &[a, b, c]), Relation::Subset);
+        let (first, second) = (header(&[a, b, c]), header(&[c, b, a]));
+        let comparison = compare(&first, &second);
+        assert_eq!(comparison.relation, Relation::Reordered);
+        let moved: Vec<_> = comparison
+            .differences
+            .iter()
+            .map(|d| match d {
+                Difference::Moved(seq, from, to) => (seq.name(), *from, *to),
+                other => panic!("{other:?}"),
+            })
+            .collect();
+        assert_eq!(moved, [("a", 0, 2), ("c", 2, 0)]);
+    }
+
+    #[test]
+    fn reports_sequences_matched_by_alias_or_checksum_as_renamed() {
+        let chr1 = format!("SN:chr1\tLN:10\tM5:{M5_A}");
+        let one = format!("SN:1\tLN:10\tM5:{M5_A}");
+        assert_eq!(relation(&[&chr1], &[&one]), Relation::Renamed);
+        assert_eq!(
+            relation(&["SN:chr1\tLN:10\tAN:1,one"], &["SN:one\tLN:10"]),
+            Relation::Renamed
+        );
+        assert_eq!(
+            relation(&["SN:chr1\tLN:10"], &["SN:1\tLN:10"]),
+            Relation::Partial
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_lengths_and_checksums() {
+        assert_eq!(
+            relation(&["SN:a\tLN:10"], &["SN:a\tLN:11"]),
+            Relation::Conflicting
+        );
+        let a = format!("SN:a\tLN:10\tM5:{M5_A}");
+        let other = format!("SN:a\tLN:10\tM5:{M5_B}");
+        let upper = format!("SN:a\tLN:10\tM5:{}", M5_A.to_uppercase());
+        assert_eq!(relation(&[&a], &[&other]), Relation::Conflicting);
+        assert_eq!(relation(&[&a], &[&upper]), Relation::Identical);
+        assert_eq!(relation(&[&a], &["SN:a\tLN:10"]), Relation::Identical);
+    }
+}
@@ -4,7 +4,8 @@ mod read_group;
 mod ref_seq;
 
 use crate::header::{Header, HeaderMeta, Program, ReadGroup, ReferenceSeq};
-use std::{collections::HashMap, str::FromStr};
+use indexmap::IndexMap;
+use std::{fmt, str::FromStr};
 
 #[derive(Debug)]
 enum RecordCode {
@@ -68,6 +69,100 @@ pub enum ParseError {
     MissingProgramId,
     IOError,
     MissingAlignmentField,
+    BadCigar,
+    UnknownReference,
+    Unsorted,
+    BadRegion,
+    BadIndex,
+    BadBgzf,
+    BadLineLength,
+    BadTag,
+    BadFastq,
+    MissingTag,
+    UnknownKey,
+    KeyInUse,
+    /// A PP naming no @PG line
+    DanglingPrevious {
+        program: String,
+        previous: String,
+    },
+    ProgramCycle {
+        program: String,
+    },
+    BadDate {
+        read_group: String,
+        value: String,
+    },
+    BadFlowOrder {
+        read_group: String,
+        value: String,
+    },
+    BadKeySequence {
+        read_group: String,
+        value: String,
+    },
+    /// An @RG PG naming no @PG line
+    DanglingProgram {
+        read_group: String,
+        value: String,
+    },
+    PositionOutOfRange(u32),
+    /// A record disagreeing with the header, in strict mode
+    InvalidRecord {
+        record: u64,
+        query_name: String,
+        problem: String,
+    },
+    /// A record that could not be parsed
+    BadRecord {
+        record: u64,
+        error: Box<ParseError>,
+    },
+    BadFlag,
+    BadSequence,
+    BadQuality,
+    LengthMismatch,
+}
+
+/// Errors carrying values name them; the others print their variant name.
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadDate { read_group, value } => {
+                write!(
+                    f,
+                    "DT {value} of read group {read_group} is not an ISO 8601 date"
+                )
+            }
+            Self::BadFlowOrder { read_group, value } => {
+                write!(
+                    f,
+                    "FO {value} of read group {read_group} is not * or IUPAC codes"
+                )
+            }
+            Self::BadKeySequence { read_group, value } => write!(
+                f,
+                "KS {value} of read group {read_group} is not read by the first flows of FO"
+            ),
+            Self::DanglingProgram { read_group, value } => {
+                write!(f, "PG {value} of read group {read_group} names no @PG line")
+            }
+            Self::DanglingPrevious { program, previous } => {
+                write!(f, "PP {previous} of program {program} names no @PG line")
+            }
+            Self::ProgramCycle { program } => {
+                write!(f, "the PP links from program {program} form a cycle")
+            }
+            Self::PositionOutOfRange(pos) => write!(f, "position {pos} is out of range"),
+            Self::InvalidRecord {
+                record,
+                query_name,
+                problem,
+            } => write!(f, "record {record} ({query_name}): {problem}"),
+            Self::BadRecord { record, error } => write!(f, "record {record}: {error}"),
+            other => write!(f, "{other:?}"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -94,9 +189,9 @@ enum HeaderRowKind {
 
 pub(crate) fn parse(s: &str) -> Result<Header, ParseError> {
     let mut meta = None;
-    let mut reference_seqs = HashMap::new();
-    let mut read_groups = HashMap::new();
-    let mut programs = HashMap::new();
+    let mut reference_seqs = IndexMap::new();
+    let mut read_groups = IndexMap::new();
+    let mut programs = IndexMap::new();
     let mut comments = Vec::new();
 
     for line in s.lines() {
@@ -136,6 +231,13 @@ pub(crate) fn parse(s: &str) -> Result<Header, ParseError> {
     })
 }
 
+pub(crate) fn parse_read_group_fields(s: &str) -> Result<ReadGroup, ParseError> {
+    let line = format!("\t{s}");
+    read_group::parse_read_group(&mut line.as_bytes())
+}
+
+pub(crate) use read_group::{is_flow_order, parse_date_time};
+
 pub(super) fn parse_header_row(mut s: &[u8]) -> Result<HeaderRow, ParseError> {
     eat_prefix(&mut s)?;
     let row_kind = parse_header_row_kind(&mut s)?;
@@ -179,7 +281,9 @@ pub(crate) fn try_insert_once<T>(opt: &mut Option<T>, value: T) -> Result<(), Pa
 
 fn parse_comment(s: &mut &[u8]) -> Result<String, ParseError> {
     // Comments can contain \t ? Assume comment goes until the end of line
-    let comment = String::from_utf8(s.to_owned()).map_err(|_| ParseError::InvalidUTF8)?;
+    // The tab after @CO separates it from the comment, which the writer puts back
+    let text = s.strip_prefix(b"\t").unwrap_or(s);
+    let comment = String::from_utf8(text.to_owned()).map_err(|_| ParseError::InvalidUTF8)?;
     *s = b"";
     Ok(comment)
 }
@@ -1,5 +1,5 @@
 use crate::header::{
-    Platform, ReadGroup,
+    DateTime, Platform, ProgramID, ReadGroup, Time,
     parser::{
         ParseError, eat_field_delimiter, eat_kv_separator, parse_str, parse_tag, parse_value,
         try_insert_once,
@@ -15,7 +15,7 @@ pub(super) fn parse_read_group(s: &mut &[u8]) -> Result<ReadGroup, ParseError> {
     let mut flow_order = None;
     let mut key_sequence = None;
     let mut library = None;
-    let mut programs = None;
+    let mut program = None;
     let mut insert_size = None;
     let mut platform = None;
     let mut platform_model = None;
@@ -32,12 +32,12 @@ pub(super) fn parse_read_group(s: &mut &[u8]) -> Result<ReadGroup, ParseError> {
             b"BC" => try_insert_once(&mut barcode, parse_str(s)?.into())?,
             b"CN" => try_insert_once(&mut center, parse_str(s)?.into())?,
             b"DS" => try_insert_once(&mut description, parse_str(s)?.into())?,
+            // DT and FO are kept as written and checked by `Header::check_read_groups`
             b"DT" => try_insert_once(&mut date, parse_str(s)?.into())?,
             b"FO" => try_insert_once(&mut flow_order, parse_str(s)?.into())?,
             b"KS" => try_insert_once(&mut key_sequence, parse_str(s)?.into())?,
             b"LB" => try_insert_once(&mut library, parse_str(s)?.into())?,
-            // TODO: unclear what format the PG field is in
-            b"PG" => try_insert_once(&mut programs, parse_str(s)?.into())?,
+            b"PG" => try_insert_once(&mut program, ProgramID(parse_str(s)?.into()))?,
             b"PI" => try_insert_once(
                 &mut insert_size,
                 parse_str(s)?
@@ -62,7 +62,7 @@ pub(super) fn parse_read_group(s: &mut &[u8]) -> Result<ReadGroup, ParseError> {
         flow_order,
         key_sequence,
         library,
-        programs,
+        program,
         insert_size,
         platform,
         platform_model,
@@ -89,3 +89,214 @@ fn parse_plaform(s: &mut &[u8]) -> Result<Platform, ParseError> {
         _ => Err(ParseError::UnknownValue),
     }
 }
+
+/// Whether `s` is a valid FO: `*` or IUPAC nucleotide codes.
+pub(crate) fn is_flow_order(s: &str) -> bool {
+    s == "*" || (!s.is_empty() && s.bytes().all(|b| b"ACMGRSVTWYHKDBN".contains(&b)))
+}
+
+/// Takes a fixed number of digits from the front of `s`.
+fn take_number<T: TryFrom<u32>>(s: &mut &str, digits: usize) -> Option<T> {
+    if s.len() < digits || !s.as_bytes()[..digits].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let (number, rest) = s.split_at(digits);
+    *s = rest;
+    T::try_from(number.parse::<u32>().ok()?).ok()
+}
+
+fn eat_char(s: &mut &str, c: char) -> bool {
+    match s.strip_prefix(c) {
+        Some(rest) => {
+            *s = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses an ISO 8601 date in extended format, `YYYY-MM-DD`, optionally followed by a time
+/// `Thh:mm[:ss[.fff]]` and a UTC offset `Z`, `±hh`, `±hh:mm` or `±hhmm`.
+pub(crate) fn parse_date_time(mut s: &str) -> Option<DateTime> {
+    let s = &mut s;
+    let year = take_number(s, 4)?;
+    if !eat_char(s, '-') {
+        return None;
+    }
+    let month = take_number(s, 2)?;
+    if !eat_char(s, '-') {
+        return None;
+    }
+    let day = take_number(s, 2)?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let time = if s.is_empty() {
+        None
+    } else if eat_char(s, 'T') {
+        Some(parse_time(s)?)
+    } else {
+        return None;
+    };
+    Some(DateTime {
+        year,
+        month,
+        day,
+        time,
+    })
+}
+
+fn parse_time(s: &mut &str) -> Option<Time> {
+    let hour = take_number(s, 2)?;
+    if !eat_char(s, ':') {
+        return None;
+    }
+    let minute = take_number(s, 2)?;
+    let mut second = 0;
+    let mut nanosecond = 0;
+    if eat_char(s, ':') {
+        second = take_number(s, 2)?;
+        if eat_char(s, '.') || eat_char(s, ',') {
+            let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return None;
+            }
+            // Digits beyond nanoseconds are dropped
+            let fraction: u32 = take_number(s, digits.min(9))?;
+            nanosecond = fraction * 10u32.pow(9 - digits.min(9) as u32);
+            *s = s.trim_start_matches(|c: char| c.is_ascii_digit());
+        }
+    }
+    // 60 seconds allows for leap seconds
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let utc_offset = if s.is_empty() {
+        None
+    } else if eat_char(s, 'Z') {
+        Some(0)
+    } else {
+        let sign = if eat_char(s, '+') {
+            1
+        } else if eat_char(s, '-') {
+            -1
+        } else {
+            return None;
+        };
+        let hours: i16 = take_number(s, 2)?;
+        eat_char(s, ':');
+        let minutes: i16 = if s.is_empty() { 0 } else { take_number(s, 2)? };
+        if hours > 23 || minutes > 59 {
+            return None;
+        }
+        Some(sign * (hours * 60 + minutes))
+    };
+    if !s.is_empty() {
+        return None;
+    }
+    Some(Time {
+        hour,
+        minute,
+        second,
+        nanosecond,
+        utc_offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> Time {
+        parse_date_time(s).unwrap().time.unwrap()
+    }
+
+    #[test]
+    fn parses_dates() {
+        let date = parse_date_time("2024-02-29").unwrap();
+        assert_eq!(
+            (date.year, date.month, date.day, date.time),
+            (2024, 2, 29, None)
+        );
+        assert!(parse_date_time("2000-02-29").is_some());
+        for s in [
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-13-01",
+            "2024-00-10",
+        ] {
+            assert!(parse_date_time(s).is_none(), "{s}");
+        }
+        for s in [
+            "2024-1-01",
+            "20240101",
+            "2024/01/01",
+            "2024-01-01 ",
+            "",
+            "2024-01-00",
+        ] {
+            assert!(parse_date_time(s).is_none(), "{s}");
+        }
+    }
+
+    #[test]
+    fn parses_times_and_offsets() {
+        let t = time("2024-01-02T03:04");
+        assert_eq!((t.hour, t.minute, t.second, t.utc_offset), (3, 4, 0, None));
+        assert_eq!(time("2024-01-02T03:04:05Z").utc_offset, Some(0));
+        assert_eq!(time("2024-01-02T03:04:05+05:30").utc_offset, Some(330));
+        assert_eq!(time("2024-01-02T03:04:05-0800").utc_offset, Some(-480));
+        assert_eq!(time("2024-01-02T03:04:05+01").utc_offset, Some(60));
+        assert_eq!(time("2024-12-31T23:59:60Z").second, 60);
+    }
+
+    #[test]
+    fn parses_fractional_seconds() {
+        assert_eq!(time("2024-01-02T03:04:05.5").nanosecond, 500_000_000);
+        assert_eq!(time("2024-01-02T03:04:05,000001").nanosecond, 1_000);
+        assert_eq!(
+            time("2024-01-02T03:04:05.1234567891Z").nanosecond,
+            123_456_789
+        );
+        assert!(parse_date_time("2024-01-02T03:04:05.").is_none());
+    }
+
+    #[test]
+    fn rejects_bad_times() {
+        for s in [
+            "2024-01-02T",
+            "2024-01-02T24:00",
+            "2024-01-02T23:60",
+            "2024-01-02T23:59:61",
+            "2024-01-02T0304",
+            "2024-01-02T03:04Y",
+            "2024-01-02T03:04+24:00",
+            "2024-01-02T03:04+01:60",
+            "2024-01-02T03:04:05Z ",
+        ] {
+            assert!(parse_date_time(s).is_none(), "{s}");
+        }
+    }
+
+    #[test]
+    fn checks_flow_orders() {
+        assert!(is_flow_order("*"));
+        assert!(is_flow_order("TACGN"));
+        assert!(!is_flow_order(""));
+        assert!(!is_flow_order("tacg"));
+        assert!(!is_flow_order("TAC*"));
+    }
+}
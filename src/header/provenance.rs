@@ -0,0 +1,115 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::header::{Header, Program, ProgramID, parser::ParseError};
+
+/// The @PG lines of a header as the graph formed by their PP links, checked to be a forest:
+/// every PP refers to an existing program and following them never loops.
+pub(crate) struct ProgramGraph<'a> {
+    programs: &'a IndexMap<ProgramID, Program>,
+    // Programs whose PP is the key, in header order
+    children: IndexMap<&'a ProgramID, Vec<&'a Program>>,
+}
+
+impl<'a> ProgramGraph<'a> {
+    pub(crate) fn new(header: &'a Header) -> Result<Self, ParseError> {
+        let programs = &header.programs;
+        let mut children: IndexMap<&ProgramID, Vec<&Program>> = IndexMap::new();
+        for program in programs.values() {
+            if let Some(previous) = &program.previous {
+                if !programs.contains_key(previous) {
+                    return Err(ParseError::DanglingPrevious {
+                        program: program.id.0.clone(),
+                        previous: previous.0.clone(),
+                    });
+                }
+                children.entry(previous).or_default().push(program);
+            }
+        }
+        // Each program has at most one PP, so a chain longer than the number of programs loops
+        for program in programs.values() {
+            let mut current = program;
+            let mut steps = 0;
+            while let Some(previous) = &current.previous {
+                steps += 1;
+                if steps > programs.len() {
+                    return Err(ParseError::ProgramCycle {
+                        program: program.id.0.clone(),
+                    });
+                }
+                current = &programs[previous];
+            }
+        }
+        Ok(Self { programs, children })
+    }
+
+    /// Programs without a PP, which start a pipeline.
+    pub(crate) fn roots(&self) -> impl Iterator<Item = &'a Program> {
+        self.programs.values().filter(|p| p.previous.is_none())
+    }
+
+    /// Programs no other program follows, which end a pipeline.
+    pub(crate) fn leaves(&self) -> impl Iterator<Item = &'a Program> {
+        self.programs
+            .values()
+            .filter(|p| !self.children.contains_key(&p.id))
+    }
+
+    /// Programs in an order where each comes after its PP, keeping header order otherwise.
+    pub(crate) fn topological(&self) -> Vec<&'a Program> {
+        let mut order = Vec::with_capacity(self.programs.len());
+        let mut stack: Vec<&Program> = self.roots().collect();
+        stack.reverse();
+        while let Some(program) = stack.pop() {
+            order.push(program);
+            if let Some(children) = self.children.get(&program.id) {
+                stack.extend(children.iter().rev());
+            }
+        }
+        order
+    }
+
+    /// Every pipeline from a root to a leaf, each listed from its first program.
+    pub(crate) fn chains(&self) -> Vec<Vec<&'a Program>> {
+        self.leaves()
+            .map(|leaf| {
+                let mut chain = vec![leaf];
+                while let Some(previous) = &chain.last().unwrap().previous {
+                    chain.push(&self.programs[previous]);
+                }
+                chain.reverse();
+                chain
+            })
+            .collect()
+    }
+}
+
+/// Lists each pipeline as numbered steps with the program name, version and command line.
+impl fmt::Display for ProgramGraph<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, chain) in self.chains().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "Pipeline {}:", i + 1)?;
+            for (step, program) in chain.iter().enumerate() {
+                write!(f, "  {}. {}", step + 1, program.id.0)?;
+                match (&program.name, &program.version) {
+                    (Some(name), Some(version)) => write!(f, " ({name} {version})")?,
+                    (Some(name), None) => write!(f, " ({name})")?,
+                    (None, Some(version)) => write!(f, " (version {version})")?,
+                    (None, None) => {}
+                }
+                writeln!(f)?;
+                if let Some(command_line) = &program.command_line {
+                    writeln!(f, "     $ {command_line}")?;
+                }
+                if let Some(description) = &program.description {
+                    writeln!(f, "     {description}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, io::BufRead};
+use std::io::BufRead;
+
+use indexmap::IndexMap;
 
 use crate::header::{
     Header,
@@ -7,20 +9,24 @@ use crate::header::{
 
 pub fn read_header(reader: &mut impl BufRead) -> Result<Header, ParseError> {
     let mut meta = None;
-    let mut reference_seqs = HashMap::new();
-    let mut read_groups = HashMap::new();
-    let mut programs = HashMap::new();
+    let mut reference_seqs = IndexMap::new();
+    let mut read_groups = IndexMap::new();
+    let mut programs = IndexMap::new();
     let mut comments = Vec::new();
 
     let mut buf = Vec::new();
     // TODO: improve error handling
-    while reader
-        .read_until(b'\n', &mut buf)
-        .map_err(|_| ParseError::IOError)?
-        > 0
+    // Stop at the first line that is not a header line, leaving it in the reader for the alignments
+    while reader.fill_buf().map_err(|_| ParseError::IOError)?.first() == Some(&b'@')
+        && reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|_| ParseError::IOError)?
+            > 0
     {
         // Remove the newline to match functionality of String::lines()
-        let header_row = parse_header_row(&buf[..buf.len() - 1])?;
+        let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let header_row = parse_header_row(line)?;
         match header_row {
             HeaderRow::Meta(m) => try_insert_once(&mut meta, m)?,
             HeaderRow::RefSeq(ref_seq) => {
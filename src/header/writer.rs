@@ -0,0 +1,140 @@
+use std::io::{self, Write};
+
+use crate::header::{
+    AlignmentGrouping, Header, HeaderMeta, Platform, Program, ReadGroup, ReferenceSeq, SortOrder,
+    Topology,
+};
+
+/// Writes the header as SAM text: @HD, then all @SQ, @RG, @PG and @CO lines.
+pub(crate) fn write_header(w: &mut impl Write, header: &Header) -> io::Result<()> {
+    if let Some(meta) = &header.meta {
+        write_meta(w, meta)?;
+    }
+    for ref_seq in header.reference_seqs.values() {
+        write_ref_seq(w, ref_seq)?;
+    }
+    for read_group in header.read_groups.values() {
+        write_read_group(w, read_group)?;
+    }
+    for program in header.programs.values() {
+        write_program(w, program)?;
+    }
+    for comment in &header.comments {
+        writeln!(w, "@CO\t{comment}")?;
+    }
+    Ok(())
+}
+
+fn write_field(w: &mut impl Write, tag: &str, value: Option<&str>) -> io::Result<()> {
+    match value {
+        Some(value) => write!(w, "\t{tag}:{value}"),
+        None => Ok(()),
+    }
+}
+
+fn write_meta(w: &mut impl Write, meta: &HeaderMeta) -> io::Result<()> {
+    let version = &meta.format_version;
+    write!(w, "@HD\tVN:{}.{}", version.major, version.minor)?;
+    write_field(
+        w,
+        "SO",
+        meta.alignment_sort_order.as_ref().map(sort_order_str),
+    )?;
+    write_field(w, "GO", meta.alignment_grouping.as_ref().map(grouping_str))?;
+    write_field(w, "SS", meta.alignment_sub_sorting.as_deref())?;
+    writeln!(w)
+}
+
+fn write_ref_seq(w: &mut impl Write, ref_seq: &ReferenceSeq) -> io::Result<()> {
+    write!(w, "@SQ\tSN:{}\tLN:{}", ref_seq.name, ref_seq.length)?;
+    write_field(w, "AH", ref_seq.alternate_locus.as_deref())?;
+    write_field(
+        w,
+        "AN",
+        ref_seq
+            .alternate_names
+            .as_ref()
+            .map(|n| n.join(","))
+            .as_deref(),
+    )?;
+    write_field(w, "AS", ref_seq.assembly_id.as_deref())?;
+    write_field(w, "DS", ref_seq.description.as_deref())?;
+    write_field(w, "M5", ref_seq.checksum.as_deref())?;
+    write_field(w, "SP", ref_seq.species.as_deref())?;
+    write_field(w, "TP", ref_seq.topology.as_ref().map(topology_str))?;
+    write_field(w, "UR", ref_seq.uri.as_deref())?;
+    writeln!(w)
+}
+
+fn write_read_group(w: &mut impl Write, read_group: &ReadGroup) -> io::Result<()> {
+    write!(w, "@RG\tID:{}", read_group.id)?;
+    write_field(w, "BC", read_group.barcode.as_deref())?;
+    write_field(w, "CN", read_group.center.as_deref())?;
+    write_field(w, "DS", read_group.description.as_deref())?;
+    write_field(w, "DT", read_group.date.as_deref())?;
+    write_field(w, "FO", read_group.flow_order.as_deref())?;
+    write_field(w, "KS", read_group.key_sequence.as_deref())?;
+    write_field(w, "LB", read_group.library.as_deref())?;
+    write_field(w, "PG", read_group.program.as_ref().map(|p| p.0.as_str()))?;
+    write_field(
+        w,
+        "PI",
+        read_group.insert_size.map(|i| i.to_string()).as_deref(),
+    )?;
+    write_field(w, "PL", read_group.platform.as_ref().map(platform_str))?;
+    write_field(w, "PM", read_group.platform_model.as_deref())?;
+    write_field(w, "PU", read_group.platform_unit.as_deref())?;
+    write_field(w, "SM", read_group.sample.as_deref())?;
+    writeln!(w)
+}
+
+fn write_program(w: &mut impl Write, program: &Program) -> io::Result<()> {
+    write!(w, "@PG\tID:{}", program.id.0)?;
+    write_field(w, "PN", program.name.as_deref())?;
+    write_field(w, "CL", program.command_line.as_deref())?;
+    write_field(w, "PP", program.previous.as_ref().map(|p| p.0.as_str()))?;
+    write_field(w, "DS", program.description.as_deref())?;
+    write_field(w, "VN", program.version.as_deref())?;
+    writeln!(w)
+}
+
+fn sort_order_str(sort_order: &SortOrder) -> &'static str {
+    match sort_order {
+        SortOrder::Unknown => "unknown",
+        SortOrder::Unsorted => "unsorted",
+        SortOrder::QueryName => "queryname",
+        SortOrder::Coordinate => "coordinate",
+    }
+}
+
+fn grouping_str(grouping: &AlignmentGrouping) -> &'static str {
+    match grouping {
+        AlignmentGrouping::None => "none",
+        AlignmentGrouping::Query => "query",
+        AlignmentGrouping::Reference => "reference",
+    }
+}
+
+fn topology_str(topology: &Topology) -> &'static str {
+    match topology {
+        Topology::Linear => "linear",
+        Topology::Circular => "circular",
+    }
+}
+
+fn platform_str(platform: &Platform) -> &'static str {
+    match platform {
+        Platform::Capillary => "CAPILLARY",
+        Platform::Dnbseq => "DNBSEQ",
+        Platform::Element => "ELEMENT",
+        Platform::Helicos => "HELICOS",
+        Platform::Illumina => "ILLUMINA",
+        Platform::Iontorent => "IONTORRENT",
+        Platform::LS454 => "LS454",
+        Platform::Ont => "ONT",
+        Platform::Pacbio => "PACBIO",
+        Platform::Singular => "SINGULAR",
+        Platform::Solid => "SOLID",
+        Platform::Ultima => "ULTIMA",
+    }
+}
@@ -1,24 +1,44 @@
 mod alignment;
+mod bgzf;
+mod commands;
+mod depth;
+mod fasta;
+mod fastq;
 mod header;
+mod markdup;
+mod pileup;
+mod region;
+mod sam;
 
-use std::{fs, io::BufReader, str::FromStr};
+use std::{env, process::ExitCode};
 
-use header::{Header, parser::ParseError, reader::read_header};
-use alignment::reader::read_alignments;
+use commands::CommandError;
+use sam::validation::Stringency;
 
-fn main() -> Result<(), ParseError> {
-    let sam = fs::read_to_string("examples/header.sam").unwrap();
-    let header = Header::from_str(&sam)?;
-    dbg!(header);
+const USAGE: &str = "samovar [--stringency strict|lenient|silent] <command> [options]";
 
-    let f = fs::File::open("examples/header.sam").unwrap();
-    let mut reader = BufReader::new(f);
-    let header = read_header(&mut reader)?;
-    dbg!(header);
+/// Takes the global options given before the command, then runs it.
+fn run(mut args: impl Iterator<Item = String>) -> Result<(), CommandError> {
+    let mut stringency = Stringency::Silent;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stringency" => {
+                stringency = args.next().and_then(|v| v.parse().ok()).ok_or_else(|| {
+                    CommandError::Usage("bad or missing value for --stringency".into())
+                })?;
+            }
+            _ => return commands::run(&arg, args, stringency),
+        }
+    }
+    Err(CommandError::Usage(USAGE.into()))
+}
 
-    let f = fs::File::open("examples/alignments.sam").unwrap();
-    let mut reader = BufReader::new(f);
-    let alignments = read_alignments(&mut reader)?;
-    dbg!(alignments);
-    Ok(())
+fn main() -> ExitCode {
+    match run(env::args().skip(1)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("samovar: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,493 @@
+pub mod umi;
+
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use crate::{
+    alignment::{Alignment, cigar::Cigar, position::Position, sequence::Quality},
+    header::{Header, parser::ParseError},
+    markdup::umi::{UmiMethod, canonical_duplex, cluster},
+};
+
+/// The unclipped 5' end of a read, which duplicates share regardless of clipping.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct End {
+    ref_seq_name: String,
+    pos: i64,
+    reverse: bool,
+}
+
+impl End {
+    fn new(ref_seq_name: &str, pos: Position, cigar: &Cigar, reverse: bool) -> Self {
+        let pos = i64::from(pos.get());
+        let pos = if reverse {
+            pos + i64::from(cigar.reference_len()) - 1 + i64::from(cigar.trailing_clips())
+        } else {
+            pos - i64::from(cigar.leading_clips())
+        };
+        Self {
+            ref_seq_name: ref_seq_name.into(),
+            pos,
+            reverse,
+        }
+    }
+}
+
+/// Templates which are duplicates of each other share a key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    /// Both ends of a pair with both mates mapped, in a fixed order
+    Pair(Option<String>, End, End),
+    /// A single-end read, or a paired read with an unmapped mate
+    Single(Option<String>, End),
+}
+
+/// A template competing to be the representative of its key.
+#[derive(Debug, Clone)]
+pub(crate) struct Candidate {
+    pub(crate) name: String,
+    /// Sum of base qualities of at least 15 of all reads of the template
+    pub(crate) score: i64,
+    /// Mean base quality of the read, settling equal scores
+    pub(crate) mean_quality: f64,
+    pub(crate) is_pair: bool,
+    /// UMI of the template, in top strand orientation for duplex UMIs
+    pub(crate) umi: Option<String>,
+    /// Whether READ1 of a pair is its leftmost end
+    pub(crate) is_top_strand: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DuplicateKind {
+    /// Library (PCR) duplicate
+    Library,
+    /// Optical duplicate, close to another read of its group on the flow cell
+    Optical,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DuplicateStats {
+    pub(crate) read: u64,
+    /// Secondary, supplementary and unmapped reads, which are never examined
+    pub(crate) excluded: u64,
+    pub(crate) examined: u64,
+    pub(crate) paired: u64,
+    pub(crate) single: u64,
+    pub(crate) duplicate_pair: u64,
+    pub(crate) duplicate_single: u64,
+    pub(crate) duplicate_pair_optical: u64,
+    pub(crate) duplicate_single_optical: u64,
+}
+
+impl DuplicateStats {
+    fn count(
+        &mut self,
+        duplicates: &HashMap<String, DuplicateKind>,
+        groups: &IndexMap<Key, Vec<Candidate>>,
+    ) {
+        let mut counted = HashSet::new();
+        for (key, candidates) in groups {
+            let is_pair = matches!(key, Key::Pair(..));
+            for candidate in candidates.iter().filter(|c| c.is_pair == is_pair) {
+                let Some(kind) = duplicates.get(&candidate.name) else {
+                    continue;
+                };
+                if !counted.insert(&candidate.name) {
+                    continue;
+                }
+                let optical = *kind == DuplicateKind::Optical;
+                if is_pair {
+                    self.duplicate_pair += 2;
+                    self.duplicate_pair_optical += if optical { 2 } else { 0 };
+                } else {
+                    self.duplicate_single += 1;
+                    self.duplicate_single_optical += u64::from(optical);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn duplicate_total(&self) -> u64 {
+        self.duplicate_pair + self.duplicate_single
+    }
+
+    /// Fraction of examined reads marked as duplicates.
+    pub(crate) fn percent_duplication(&self) -> f64 {
+        if self.examined == 0 {
+            return 0.0;
+        }
+        self.duplicate_total() as f64 / self.examined as f64
+    }
+
+    /// Number of distinct molecules in the library, estimated from pairs like Picard does.
+    ///
+    /// Optical duplicates are left out of the examined pairs, as they were not sampled from the
+    /// library.
+    pub(crate) fn estimated_library_size(&self) -> Option<u64> {
+        let pairs = ((self.paired - self.duplicate_pair_optical) / 2) as f64;
+        let unique = ((self.paired - self.duplicate_pair) / 2) as f64;
+        if pairs == 0.0 || unique >= pairs {
+            return None;
+        }
+        // Solves unique / x = 1 - exp(-pairs / x)
+        let f = |x: f64| unique / x - 1.0 + (-pairs / x).exp();
+        let (mut low, mut high) = (1.0, 100.0);
+        if f(low * unique) < 0.0 {
+            return None;
+        }
+        while f(high * unique) > 0.0 {
+            high *= 10.0;
+        }
+        for _ in 0..40 {
+            let mid = (low + high) / 2.0;
+            let u = f(mid * unique);
+            if u == 0.0 {
+                break;
+            } else if u > 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Some((unique * (low + high) / 2.0) as u64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DuplicateOptions {
+    /// Maximum distance in pixels between optical duplicates, `None` to not look for them
+    pub(crate) optical_distance: Option<u32>,
+    /// Splits duplicates of a position by UMI, `None` to ignore UMIs
+    pub(crate) umi: Option<UmiMethod>,
+    pub(crate) umi_tag: [u8; 2],
+    /// UMIs are `A-B` pairs, swapped between the two strands of a molecule
+    pub(crate) duplex: bool,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self {
+            optical_distance: None,
+            umi: None,
+            umi_tag: *b"RX",
+            duplex: false,
+        }
+    }
+}
+
+/// The molecule a template was assigned to by UMI grouping.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Molecule {
+    pub(crate) id: usize,
+    /// `Some(true)` for the top strand of a duplex molecule
+    pub(crate) top_strand: Option<bool>,
+    /// Number of templates of the molecule
+    pub(crate) size: usize,
+}
+
+impl Molecule {
+    /// Value of the MI tag, with a strand suffix `/A` or `/B` for duplex molecules.
+    pub(crate) fn identifier(&self) -> String {
+        match self.top_strand {
+            Some(true) => format!("{}/A", self.id),
+            Some(false) => format!("{}/B", self.id),
+            None => self.id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Duplicates {
+    pub(crate) kinds: HashMap<String, DuplicateKind>,
+    /// Molecule of each template, only filled in when grouping by UMI
+    pub(crate) molecules: HashMap<String, Molecule>,
+    pub(crate) stats: DuplicateStats,
+}
+
+/// Groups the primary reads of templates by their unclipped 5' ends, library and orientation.
+pub(crate) struct DuplicateFinder<'h> {
+    header: &'h Header,
+    options: DuplicateOptions,
+    groups: IndexMap<Key, Vec<Candidate>>,
+    seen_pairs: HashSet<String>,
+    stats: DuplicateStats,
+}
+
+impl<'h> DuplicateFinder<'h> {
+    pub(crate) fn new(header: &'h Header, options: DuplicateOptions) -> Self {
+        Self {
+            header,
+            options,
+            groups: IndexMap::new(),
+            seen_pairs: HashSet::new(),
+            stats: DuplicateStats::default(),
+        }
+    }
+
+    /// Adds a read, which needs MC and ms tags if its mate is mapped, as added by `fixmate`.
+    pub(crate) fn add(&mut self, alignment: &Alignment) -> Result<(), ParseError> {
+        self.stats.read += 1;
+        let flag = alignment.flag();
+        let position = alignment.position().filter(|_| !flag.is_unmapped());
+        let Some(position) = position.filter(|_| flag.is_primary_line()) else {
+            self.stats.excluded += 1;
+            return Ok(());
+        };
+        self.stats.examined += 1;
+
+        let library = alignment
+            .tag(b"RG")
+            .and_then(|rg| rg.value.as_str())
+            .and_then(|id| self.header.read_group(id))
+            .and_then(|rg| rg.library())
+            .map(str::to_owned);
+        let end = End::new(
+            alignment.ref_seq_name(),
+            position,
+            alignment.cigar(),
+            flag.is_reverse_complement(),
+        );
+        let score = quality_score(alignment);
+        let mean_quality = alignment.quality().and_then(Quality::mean).unwrap_or(0.0);
+        let umi = self
+            .options
+            .umi
+            .and(alignment.tag(&self.options.umi_tag))
+            .and_then(|t| t.value.as_str());
+
+        let mate_position = alignment
+            .mate_position()
+            .filter(|_| flag.has_multiple_segments() && !flag.next_is_unmapped());
+        let Some(mate_position) = mate_position else {
+            self.stats.single += 1;
+            self.push(
+                Key::Single(library, end),
+                Candidate {
+                    name: alignment.query_name().into(),
+                    score,
+                    mean_quality,
+                    is_pair: false,
+                    umi: umi.map(str::to_owned),
+                    is_top_strand: true,
+                },
+            );
+            return Ok(());
+        };
+
+        self.stats.paired += 1;
+        let mate_cigar: Cigar = alignment
+            .tag(b"MC")
+            .and_then(|t| t.value.as_str())
+            .ok_or(ParseError::MissingTag)?
+            .parse()?;
+        let mate_ref = if alignment.mate_on_same_reference() {
+            alignment.ref_seq_name()
+        } else {
+            alignment.rnext()
+        };
+        let mate_end = End::new(
+            mate_ref,
+            mate_position,
+            &mate_cigar,
+            flag.next_is_reverse_complement(),
+        );
+        let mate_score = alignment
+            .tag(b"ms")
+            .and_then(|t| t.value.as_int())
+            .unwrap_or(0);
+
+        let is_top_strand = flag.is_first_segment() == (end <= mate_end);
+        let umi = match umi {
+            Some(umi) if self.options.duplex => Some(canonical_duplex(umi, is_top_strand)),
+            umi => umi.map(str::to_owned),
+        };
+        let candidate = Candidate {
+            name: alignment.query_name().into(),
+            score: score + mate_score,
+            mean_quality,
+            is_pair: true,
+            umi,
+            is_top_strand,
+        };
+        // Pairs occupy the single positions of their reads, making single reads there duplicates
+        self.push(Key::Single(library.clone(), end.clone()), candidate.clone());
+        if self.seen_pairs.insert(alignment.query_name().into()) {
+            let (a, b) = if end <= mate_end {
+                (end, mate_end)
+            } else {
+                (mate_end, end)
+            };
+            self.push(Key::Pair(library, a, b), candidate);
+        } else {
+            self.seen_pairs.remove(alignment.query_name());
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, key: Key, candidate: Candidate) {
+        self.groups.entry(key).or_default().push(candidate);
+    }
+
+    /// Templates sharing a key, with whether the key is of a pair.
+    pub(crate) fn groups(&self) -> impl Iterator<Item = (bool, &[Candidate])> {
+        self.groups
+            .iter()
+            .map(|(key, candidates)| (matches!(key, Key::Pair(..)), candidates.as_slice()))
+    }
+
+    /// Decides which templates are duplicates, and which molecule they are from if grouping by
+    /// UMI.
+    pub(crate) fn finish(mut self) -> Duplicates {
+        let mut kinds = HashMap::new();
+        let mut molecules = HashMap::new();
+        let mut next_id = 0;
+        for (is_pair, candidates) in self.groups() {
+            for group in self.split_by_umi(candidates) {
+                mark_group(&group, is_pair, &self.options, &mut kinds);
+                if self.options.umi.is_some() {
+                    let members: Vec<_> = group.iter().filter(|c| c.is_pair == is_pair).collect();
+                    for member in &members {
+                        let molecule = Molecule {
+                            id: next_id,
+                            top_strand: (self.options.duplex && is_pair)
+                                .then_some(member.is_top_strand),
+                            size: members.len(),
+                        };
+                        molecules.insert(member.name.clone(), molecule);
+                    }
+                    next_id += usize::from(!members.is_empty());
+                }
+            }
+        }
+        self.stats.count(&kinds, &self.groups);
+        Duplicates {
+            kinds,
+            molecules,
+            stats: self.stats,
+        }
+    }
+
+    /// Splits templates sharing a key into molecules by UMI, templates without one sharing a
+    /// molecule.
+    fn split_by_umi<'a>(&self, candidates: &'a [Candidate]) -> Vec<Vec<&'a Candidate>> {
+        let Some(method) = self.options.umi else {
+            return vec![candidates.iter().collect()];
+        };
+        let umis: Vec<&str> = candidates
+            .iter()
+            .map(|c| c.umi.as_deref().unwrap_or(""))
+            .collect();
+        let mut groups: Vec<Vec<&Candidate>> = Vec::new();
+        for (candidate, i) in candidates.iter().zip(cluster(&umis, method)) {
+            if groups.len() <= i {
+                groups.resize_with(i + 1, Vec::new);
+            }
+            groups[i].push(candidate);
+        }
+        groups
+    }
+}
+
+/// Marks the templates of a group as duplicates, keeping the best scoring one.
+///
+/// In a single-read group only single reads are marked, and all of them if a pair shares
+/// their end. Equal scores go to the higher mean base quality, then to the template seen first.
+pub(crate) fn mark_group(
+    candidates: &[&Candidate],
+    is_pair: bool,
+    options: &DuplicateOptions,
+    duplicates: &mut HashMap<String, DuplicateKind>,
+) {
+    let markable: Vec<_> = candidates.iter().filter(|c| c.is_pair == is_pair).collect();
+    let has_pair = !is_pair && markable.len() < candidates.len();
+    let best = markable
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by(|(_, a), (_, b)| {
+            a.score
+                .cmp(&b.score)
+                .then(a.mean_quality.total_cmp(&b.mean_quality))
+        })
+        .map(|(i, _)| i)
+        .filter(|_| !has_pair);
+
+    for (i, candidate) in markable.iter().enumerate() {
+        if Some(i) == best {
+            continue;
+        }
+        let is_optical = options.optical_distance.is_some_and(|distance| {
+            candidates
+                .iter()
+                .filter(|other| other.name != candidate.name)
+                .any(|other| is_optical_pair(&candidate.name, &other.name, distance))
+        });
+        let kind = if is_optical {
+            DuplicateKind::Optical
+        } else {
+            DuplicateKind::Library
+        };
+        duplicates.insert(candidate.name.clone(), kind);
+    }
+}
+
+fn is_optical_pair(a: &str, b: &str, distance: u32) -> bool {
+    let (Some((tile_a, xa, ya)), Some((tile_b, xb, yb))) = (tile_position(a), tile_position(b))
+    else {
+        return false;
+    };
+    tile_a == tile_b && xa.abs_diff(xb) <= distance && ya.abs_diff(yb) <= distance
+}
+
+/// Sum of the base qualities of at least 15, which duplicates are ranked by and fixmate stores in
+/// the `ms` tag.
+pub(crate) fn quality_score(alignment: &Alignment) -> i64 {
+    alignment.quality().map_or(0, |q| {
+        q.scores()
+            .iter()
+            .map(|&q| i64::from(q))
+            .filter(|&q| q >= 15)
+            .sum()
+    })
+}
+
+/// Position of a read on the flow cell, from an Illumina read name.
+///
+/// The name ends in `<lane>:<tile>:<x>:<y>`; everything before x identifies the tile.
+pub(crate) fn tile_position(name: &str) -> Option<(&str, u32, u32)> {
+    let (rest, y) = name.rsplit_once(':')?;
+    let (tile, x) = rest.rsplit_once(':')?;
+    // Needs at least the lane before the tile
+    tile.contains(':').then_some(())?;
+    Some((tile, x.parse().ok()?, y.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_library_size_like_picard() {
+        let mut stats = DuplicateStats {
+            paired: 20_000,
+            duplicate_pair: 5_000,
+            ..DuplicateStats::default()
+        };
+        // Picard's estimateLibrarySize(10000, 7500)
+        assert_eq!(stats.estimated_library_size(), Some(16_505));
+        // Optical duplicates don't count as examined, estimateLibrarySize(9500, 7500)
+        stats.duplicate_pair_optical = 1_000;
+        assert_eq!(stats.estimated_library_size(), Some(19_270));
+    }
+
+    #[test]
+    fn estimates_no_library_size_without_duplicates() {
+        let stats = DuplicateStats {
+            paired: 20_000,
+            ..DuplicateStats::default()
+        };
+        assert_eq!(stats.estimated_library_size(), None);
+        assert_eq!(DuplicateStats::default().estimated_library_size(), None);
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use indexmap::IndexMap;
+
+/// How reads of the same position are split into molecules by their UMI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UmiMethod {
+    /// Only identical UMIs
+    Exact,
+    /// UMIs linked by chains of at most the given edit distance
+    EditDistance(u32),
+    /// Directional adjacency as in UMI-tools: a UMI absorbs those one edit away with at most
+    /// half its count (plus one)
+    Directional,
+}
+
+/// Number of differing bases between two UMIs of the same length, `None` for different lengths.
+///
+/// As in UMI-tools, UMIs are only compared by substitutions.
+fn edit_distance(a: &str, b: &str) -> Option<u32> {
+    (a.len() == b.len()).then(|| a.bytes().zip(b.bytes()).filter(|(x, y)| x != y).count() as u32)
+}
+
+/// Assigns a cluster index to each UMI, numbering clusters from 0 in order of their largest UMI.
+pub(crate) fn cluster(umis: &[&str], method: UmiMethod) -> Vec<usize> {
+    let mut counts: IndexMap<&str, usize> = IndexMap::new();
+    for umi in umis {
+        *counts.entry(umi).or_default() += 1;
+    }
+    // Most frequent first, keeping the order of appearance on ties
+    counts.sort_by(|_, a, _, b| b.cmp(a));
+    let nodes: Vec<(&str, usize)> = counts.iter().map(|(&u, &c)| (u, c)).collect();
+
+    let linked = |a: usize, b: usize| {
+        let (umi_a, count_a) = nodes[a];
+        let (umi_b, count_b) = nodes[b];
+        match method {
+            UmiMethod::Exact => false,
+            UmiMethod::EditDistance(d) => edit_distance(umi_a, umi_b).is_some_and(|e| e <= d),
+            UmiMethod::Directional => {
+                edit_distance(umi_a, umi_b) == Some(1) && count_a + 1 >= 2 * count_b
+            }
+        }
+    };
+
+    let mut node_cluster = vec![usize::MAX; nodes.len()];
+    let mut clusters = 0;
+    for start in 0..nodes.len() {
+        if node_cluster[start] != usize::MAX {
+            continue;
+        }
+        node_cluster[start] = clusters;
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for (next, assigned) in node_cluster.iter_mut().enumerate() {
+                if *assigned == usize::MAX && linked(node, next) {
+                    *assigned = clusters;
+                    queue.push_back(next);
+                }
+            }
+        }
+        clusters += 1;
+    }
+
+    umis.iter()
+        .map(|umi| node_cluster[counts.get_index_of(umi).unwrap()])
+        .collect()
+}
+
+/// Puts a duplex UMI `A-B` in the orientation of the top strand.
+///
+/// Both strands of a molecule carry the two halves in opposite orders; the top strand is the one
+/// whose READ1 is the leftmost end of the pair.
+pub(crate) fn canonical_duplex(umi: &str, read1_is_left: bool) -> String {
+    match umi.split_once('-') {
+        Some((a, b)) if !read1_is_left => format!("{b}-{a}"),
+        _ => umi.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` copies of each UMI, in order.
+    fn repeat<'a>(umis: &[(&'a str, usize)]) -> Vec<&'a str> {
+        umis.iter()
+            .flat_map(|&(umi, count)| std::iter::repeat_n(umi, count))
+            .collect()
+    }
+
+    #[test]
+    fn numbers_clusters_by_count() {
+        let umis = ["AAAA", "CCCC", "CCCC", "AAAA", "CCCC", "GGGG"];
+        assert_eq!(cluster(&umis, UmiMethod::Exact), [1, 0, 0, 1, 0, 2]);
+        assert!(cluster(&[], UmiMethod::Directional).is_empty());
+    }
+
+    #[test]
+    fn chains_umis_within_the_edit_distance() {
+        let umis = ["AAAA", "AAAT", "AATT", "TTTT"];
+        assert_eq!(cluster(&umis, UmiMethod::EditDistance(1)), [0, 0, 0, 1]);
+        assert_eq!(cluster(&umis, UmiMethod::EditDistance(2)), [0, 0, 0, 0]);
+        assert_eq!(cluster(&umis, UmiMethod::EditDistance(0)), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn never_links_umis_of_different_lengths() {
+        let umis = ["AAAA", "AAA", "AAAAA"];
+        assert_eq!(cluster(&umis, UmiMethod::EditDistance(5)), [0, 1, 2]);
+    }
+
+    #[test]
+    fn directional_absorbs_umis_with_at_most_half_the_count() {
+        // 10 + 1 >= 2 * 5 but not >= 2 * 6
+        let umis = repeat(&[("AAAA", 10), ("AAAT", 5)]);
+        assert!(
+            cluster(&umis, UmiMethod::Directional)
+                .iter()
+                .all(|&c| c == 0)
+        );
+        let umis = repeat(&[("AAAA", 10), ("AAAT", 6)]);
+        assert_eq!(cluster(&umis, UmiMethod::Directional)[10], 1);
+    }
+
+    #[test]
+    fn directional_follows_chains_but_not_two_edits() {
+        let umis = repeat(&[("AAAA", 10), ("AAAT", 5), ("AATT", 2), ("ATTT", 2)]);
+        let clusters = cluster(&umis, UmiMethod::Directional);
+        assert_eq!(&clusters[..17], &[0; 17]);
+        // AATT has as many reads as ATTT, so it cannot absorb it
+        assert_eq!(&clusters[17..], &[1, 1]);
+        let umis = repeat(&[("AAAA", 10), ("AATT", 1)]);
+        assert_eq!(cluster(&umis, UmiMethod::Directional)[10], 1);
+    }
+
+    #[test]
+    fn orients_duplex_umis_by_strand() {
+        assert_eq!(canonical_duplex("AC-GT", true), "AC-GT");
+        assert_eq!(canonical_duplex("AC-GT", false), "GT-AC");
+        assert_eq!(canonical_duplex("ACGT", false), "ACGT");
+    }
+}
@@ -0,0 +1,465 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use crate::{
+    alignment::{Alignment, cigar::CigarOpKind, position::Position},
+    header::{Header, parser::ParseError},
+};
+
+/// One alignment's contribution to a pileup column.
+#[derive(Debug, Clone)]
+pub(crate) struct PileupEntry {
+    /// Index of the input the alignment came from
+    pub(crate) input: usize,
+    pub(crate) alignment: Rc<Alignment>,
+    /// 0-based offset into SEQ of the base at this position, or of the next base for deletions
+    /// and reference skips
+    pub(crate) query_pos: u32,
+    /// Phred quality of the base at `query_pos`, `None` if QUAL is absent
+    ///
+    /// This may differ from QUAL if the mates of a pair overlap, see [`OverlapMode`].
+    pub(crate) quality: Option<u8>,
+    pub(crate) is_del: bool,
+    pub(crate) is_refskip: bool,
+    /// Length of an insertion (positive) or deletion (negative) directly after this position
+    pub(crate) indel: i32,
+    /// First aligned base of the alignment
+    pub(crate) is_head: bool,
+    /// Last aligned base of the alignment
+    pub(crate) is_tail: bool,
+}
+
+impl PileupEntry {
+    /// Base at this position, `None` for deletions and reference skips.
+    pub(crate) fn base(&self) -> Option<u8> {
+        if self.is_del || self.is_refskip {
+            return None;
+        }
+        self.alignment
+            .sequence()
+            .as_bytes()
+            .get(self.query_pos as usize)
+            .copied()
+    }
+
+    /// Bases inserted after this position, empty if there is no insertion.
+    pub(crate) fn inserted_bases(&self) -> &str {
+        let Ok(len) = u32::try_from(self.indel) else {
+            return "";
+        };
+        let start = self.query_pos as usize + 1;
+        self.alignment
+            .sequence()
+            .as_str()
+            .get(start..start + len as usize)
+            .unwrap_or("")
+    }
+}
+
+/// How to treat both mates of a pair covering the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OverlapMode {
+    /// Keep both mates as independent observations
+    Count,
+    /// Keep only the mate with the higher base quality, the first one on ties
+    KeepBest,
+    /// Keep both mates but zero the quality of one of them, like samtools does
+    ///
+    /// If the bases agree the first mate gets the sum of both qualities, capped at 200. If they
+    /// disagree the mate with the higher quality keeps 80% of it.
+    ZeroQuality,
+}
+
+/// All alignments covering a single reference position.
+#[derive(Debug)]
+pub(crate) struct PileupColumn {
+    pub(crate) ref_index: usize,
+    /// 1-based reference position
+    pub(crate) pos: u32,
+    pub(crate) entries: Vec<PileupEntry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    query_pos: u32,
+    kind: CigarOpKind,
+    indel: i32,
+}
+
+#[derive(Debug)]
+struct ActiveRead {
+    input: usize,
+    alignment: Rc<Alignment>,
+    // 1-based position of the first slot
+    start: u32,
+    // One slot per reference position from `start`
+    slots: Vec<Slot>,
+}
+
+impl ActiveRead {
+    fn new(input: usize, alignment: Alignment, start: Position) -> Self {
+        let mut slots: Vec<Slot> = Vec::new();
+        let mut query_pos = 0;
+        for op in alignment.cigar().ops() {
+            match op.kind {
+                CigarOpKind::Insertion => {
+                    if let Some(last) = slots.last_mut() {
+                        last.indel = op.len as i32;
+                    }
+                }
+                CigarOpKind::Deletion => {
+                    if let Some(last) = slots.last_mut().filter(|s| s.indel == 0) {
+                        last.indel = -(op.len as i32);
+                    }
+                }
+                _ => {}
+            }
+            if op.kind.consumes_reference() {
+                slots.extend((0..op.len).map(|i| Slot {
+                    query_pos: if op.kind.consumes_query() {
+                        query_pos + i
+                    } else {
+                        query_pos
+                    },
+                    kind: op.kind,
+                    indel: 0,
+                }));
+            }
+            if op.kind.consumes_query() {
+                query_pos += op.len;
+            }
+        }
+        Self {
+            input,
+            alignment: Rc::new(alignment),
+            start: start.get(),
+            slots,
+        }
+    }
+
+    fn end(&self) -> u32 {
+        self.start + self.slots.len() as u32 - 1
+    }
+
+    fn entry(&self, pos: u32) -> Option<PileupEntry> {
+        let i = pos.checked_sub(self.start)? as usize;
+        let slot = self.slots.get(i)?;
+        let quality = self
+            .alignment
+            .quality()
+            .and_then(|q| q.scores().get(slot.query_pos as usize))
+            .copied();
+        Some(PileupEntry {
+            input: self.input,
+            alignment: Rc::clone(&self.alignment),
+            query_pos: slot.query_pos,
+            quality,
+            is_del: slot.kind == CigarOpKind::Deletion,
+            is_refskip: slot.kind == CigarOpKind::Skip,
+            indel: slot.indel,
+            is_head: i == 0,
+            is_tail: i == self.slots.len() - 1,
+        })
+    }
+}
+
+/// Turns coordinate-sorted alignments into per-position pileup columns.
+///
+/// Unmapped alignments and alignments not covering any reference base are skipped; any other filtering should
+/// be done on the input. Positions not covered by any alignment are not returned.
+pub(crate) struct Pileup<I: Iterator> {
+    header: Header,
+    alignments: std::iter::Peekable<I>,
+    active: VecDeque<ActiveRead>,
+    ref_index: usize,
+    pos: u32,
+    max_depth: Option<usize>,
+    overlaps: OverlapMode,
+}
+
+impl<I> Pileup<I>
+where
+    I: Iterator<Item = Result<(usize, Alignment), ParseError>>,
+{
+    pub(crate) fn new(header: Header, alignments: I) -> Self {
+        Self {
+            header,
+            alignments: alignments.peekable(),
+            active: VecDeque::new(),
+            ref_index: 0,
+            pos: 0,
+            max_depth: None,
+            overlaps: OverlapMode::Count,
+        }
+    }
+
+    /// Stops adding alignments of an input at a position once it has `max_depth` of them.
+    pub(crate) fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub(crate) fn with_overlaps(mut self, overlaps: OverlapMode) -> Self {
+        self.overlaps = overlaps;
+        self
+    }
+
+    pub(crate) fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Takes the next usable alignment with its reference index and start, if it starts at or
+    /// before the current position.
+    fn next_starting(&mut self, any_pos: bool) -> Option<Result<Started, ParseError>> {
+        loop {
+            let (_, alignment) = match self.alignments.peek()? {
+                Ok(peeked) => peeked,
+                Err(e) => {
+                    let e = e.clone();
+                    self.alignments.next();
+                    return Some(Err(e));
+                }
+            };
+            if alignment.flag().is_unmapped() || alignment.cigar().reference_len() == 0 {
+                self.alignments.next();
+                continue;
+            }
+            let (ref_index, start) = match locate(&self.header, alignment) {
+                Ok(located) => located,
+                Err(e) => return Some(Err(e)),
+            };
+            if !any_pos {
+                if (ref_index, start.get()) > (self.ref_index, self.pos) {
+                    return None;
+                }
+                if ref_index < self.ref_index {
+                    return Some(Err(ParseError::Unsorted));
+                }
+            }
+            let next = self.alignments.next()?;
+            return Some(next.map(|(input, alignment)| Started {
+                input,
+                alignment,
+                ref_index,
+                start,
+            }));
+        }
+    }
+
+    fn add(&mut self, started: Started) {
+        let Started {
+            input,
+            alignment,
+            start,
+            ..
+        } = started;
+        if let Some(max_depth) = self.max_depth {
+            let depth = self.active.iter().filter(|r| r.input == input).count();
+            if depth >= max_depth {
+                return;
+            }
+        }
+        self.active
+            .push_back(ActiveRead::new(input, alignment, start));
+    }
+}
+
+/// An alignment taken from the input, with where it starts.
+struct Started {
+    input: usize,
+    alignment: Alignment,
+    ref_index: usize,
+    start: Position,
+}
+
+/// The reference index and start of a mapped alignment, which must have a POS.
+fn locate(header: &Header, alignment: &Alignment) -> Result<(usize, Position), ParseError> {
+    let ref_index = header
+        .reference_seq_index(alignment.ref_seq_name())
+        .ok_or(ParseError::UnknownReference)?;
+    let start = alignment
+        .position()
+        .ok_or(ParseError::PositionOutOfRange(0))?;
+    Ok((ref_index, start))
+}
+
+impl<I> Iterator for Pileup<I>
+where
+    I: Iterator<Item = Result<(usize, Alignment), ParseError>>,
+{
+    type Item = Result<PileupColumn, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.active.is_empty() {
+            // Jump to the start of the next alignment
+            let started = match self.next_starting(true)? {
+                Ok(started) => started,
+                Err(e) => return Some(Err(e)),
+            };
+            if (started.ref_index, started.start.get()) < (self.ref_index, self.pos) {
+                return Some(Err(ParseError::Unsorted));
+            }
+            self.ref_index = started.ref_index;
+            self.pos = started.start.get();
+            self.add(started);
+        }
+        while let Some(next) = self.next_starting(false) {
+            match next {
+                Ok(started) if started.start.get() < self.pos => {
+                    return Some(Err(ParseError::Unsorted));
+                }
+                Ok(started) => self.add(started),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        let pos = self.pos;
+        let mut entries = self.active.iter().filter_map(|r| r.entry(pos)).collect();
+        resolve_overlaps(&mut entries, self.overlaps);
+        let column = PileupColumn {
+            ref_index: self.ref_index,
+            pos,
+            entries,
+        };
+
+        self.pos += 1;
+        self.active.retain(|r| r.end() >= self.pos);
+        Some(Ok(column))
+    }
+}
+
+/// Finds mates of the same input overlapping in a column and applies `mode` to them.
+///
+/// Only mates which both have a base at this position are considered.
+fn resolve_overlaps(entries: &mut Vec<PileupEntry>, mode: OverlapMode) {
+    if mode == OverlapMode::Count {
+        return;
+    }
+
+    let mut first_mates = HashMap::new();
+    let mut pairs = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if !entry.alignment.flag().has_multiple_segments() || entry.base().is_none() {
+            continue;
+        }
+        let key = (entry.input, entry.alignment.query_name());
+        match first_mates.remove(&key) {
+            Some(first) => pairs.push((first, i)),
+            None => {
+                first_mates.insert(key, i);
+            }
+        }
+    }
+
+    let mut dropped = Vec::new();
+    for (a, b) in pairs {
+        let qa = entries[a].quality.unwrap_or(0);
+        let qb = entries[b].quality.unwrap_or(0);
+        match mode {
+            OverlapMode::Count => unreachable!(),
+            OverlapMode::KeepBest => dropped.push(if qa >= qb { b } else { a }),
+            OverlapMode::ZeroQuality => {
+                let same = entries[a]
+                    .base()
+                    .zip(entries[b].base())
+                    .is_some_and(|(x, y)| x.eq_ignore_ascii_case(&y));
+                let (qa, qb) = if same {
+                    (qa.saturating_add(qb).min(200), 0)
+                } else if qa >= qb {
+                    (scale(qa), 0)
+                } else {
+                    (0, scale(qb))
+                };
+                entries[a].quality = Some(qa);
+                entries[b].quality = Some(qb);
+            }
+        }
+    }
+
+    if !dropped.is_empty() {
+        let mut i = 0;
+        entries.retain(|_| {
+            i += 1;
+            !dropped.contains(&(i - 1))
+        });
+    }
+}
+
+fn scale(quality: u8) -> u8 {
+    (f32::from(quality) * 0.8) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alignment::parser::parse_alignment;
+
+    // Mates of a pair overlapping at position 3, where the first reads G at Q40 and the second T
+    // at Q20
+    fn overlapping_pair() -> Vec<PileupEntry> {
+        [
+            ("r1\t99\tchr1\t1\t60\t4M\t=\t3\t6\tACGT\tIIII", 2),
+            ("r1\t147\tchr1\t3\t60\t4M\t=\t1\t-6\tTTGG\t5555", 0),
+        ]
+        .into_iter()
+        .map(|(line, query_pos)| {
+            let alignment = parse_alignment(line.as_bytes()).unwrap();
+            PileupEntry {
+                input: 0,
+                quality: Some(alignment.quality().unwrap().scores()[query_pos]),
+                alignment: Rc::new(alignment),
+                query_pos: query_pos as u32,
+                is_del: false,
+                is_refskip: false,
+                indel: 0,
+                is_head: false,
+                is_tail: false,
+            }
+        })
+        .collect()
+    }
+
+    fn qualities(entries: &[PileupEntry]) -> Vec<(u8, Option<u8>)> {
+        entries
+            .iter()
+            .map(|e| (e.base().unwrap(), e.quality))
+            .collect()
+    }
+
+    #[test]
+    fn counts_both_mates() {
+        let mut entries = overlapping_pair();
+        resolve_overlaps(&mut entries, OverlapMode::Count);
+        assert_eq!(qualities(&entries), [(b'G', Some(40)), (b'T', Some(20))]);
+    }
+
+    #[test]
+    fn keeps_the_better_mate() {
+        let mut entries = overlapping_pair();
+        resolve_overlaps(&mut entries, OverlapMode::KeepBest);
+        assert_eq!(qualities(&entries), [(b'G', Some(40))]);
+    }
+
+    #[test]
+    fn zeroes_the_worse_of_disagreeing_mates() {
+        let mut entries = overlapping_pair();
+        resolve_overlaps(&mut entries, OverlapMode::ZeroQuality);
+        assert_eq!(qualities(&entries), [(b'G', Some(32)), (b'T', Some(0))]);
+
+        // Agreeing mates add up on the first
+        let mut entries = overlapping_pair();
+        entries[1].query_pos = 2;
+        resolve_overlaps(&mut entries, OverlapMode::ZeroQuality);
+        assert_eq!(qualities(&entries), [(b'G', Some(60)), (b'G', Some(0))]);
+    }
+
+    #[test]
+    fn leaves_other_templates_alone() {
+        let mut entries = overlapping_pair();
+        entries[1].input = 1;
+        resolve_overlaps(&mut entries, OverlapMode::KeepBest);
+        assert_eq!(entries.len(), 2);
+    }
+}
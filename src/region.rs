@@ -0,0 +1,95 @@
+use std::{io::BufRead, str::FromStr};
+
+use crate::{alignment::position::Position, header::parser::ParseError};
+
+/// A span of a reference sequence, written `name`, `name:start` or `name:start-end`.
+///
+/// Positions are 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub(crate) name: String,
+    pub(crate) start: u32,
+    /// `None` extends the region to the end of the reference
+    pub(crate) end: Option<u32>,
+}
+
+impl Region {
+    /// The whole of the reference sequence `name`.
+    pub(crate) fn whole(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            start: 1,
+            end: None,
+        }
+    }
+
+    pub(crate) fn contains(&self, name: &str, pos: u32) -> bool {
+        self.name == name && self.start <= pos && self.end.is_none_or(|end| pos <= end)
+    }
+}
+
+impl FromStr for Region {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Names may themselves contain ':', so only split off a trailing range that parses
+        if let Some((name, range)) = s.rsplit_once(':')
+            && let Some((start, end)) = parse_range(range)
+        {
+            if name.is_empty() || end.is_some_and(|end| end < start) {
+                return Err(ParseError::BadRegion);
+            }
+            return Ok(Self {
+                name: name.into(),
+                start,
+                end,
+            });
+        }
+        if s.is_empty() {
+            return Err(ParseError::BadRegion);
+        }
+        Ok(Self::whole(s))
+    }
+}
+
+fn parse_range(s: &str) -> Option<(u32, Option<u32>)> {
+    let number = |s: &str| s.replace(',', "").parse::<u32>().ok();
+    match s.split_once('-') {
+        Some((start, "")) => Some((number(start)?.max(1), None)),
+        Some((start, end)) => Some((number(start)?.max(1), Some(number(end)?))),
+        None => Some((number(s)?.max(1), None)),
+    }
+}
+
+/// Reads the regions of a BED file, converting its 0-based half-open intervals.
+pub(crate) fn read_bed(reader: impl BufRead) -> Result<Vec<Region>, ParseError> {
+    let mut regions = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(|_| ParseError::IOError)?;
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let (Some(name), Some(start), Some(end)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(ParseError::BadRegion);
+        };
+        let start: u32 = start.parse().map_err(|_| ParseError::BadRegion)?;
+        let end: u32 = end.parse().map_err(|_| ParseError::BadRegion)?;
+        if end < start {
+            return Err(ParseError::BadRegion);
+        }
+        // BED starts are 0-based
+        let start = Position::from_offset(start as usize).ok_or(ParseError::BadRegion)?;
+        regions.push(Region {
+            name: name.into(),
+            start: start.get(),
+            end: Some(end),
+        });
+    }
+    Ok(regions)
+}
@@ -0,0 +1,4 @@
+pub mod merge;
+pub mod order;
+pub mod reader;
+pub mod validation;
@@ -0,0 +1,68 @@
+use std::{io::BufRead, iter::Peekable};
+
+use crate::{
+    alignment::{Alignment, position::Position},
+    header::{Header, parser::ParseError},
+    sam::reader::Reader,
+};
+
+/// Merges several coordinate-sorted inputs into a single coordinate-sorted stream.
+///
+/// Records are ordered by the @SQ order of the first input's header; records on references
+/// unknown to it, including unplaced records, come last. Each record is returned with the index
+/// of the input it came from.
+pub(crate) struct Merged<R: BufRead> {
+    header: Header,
+    inputs: Vec<Peekable<Reader<R>>>,
+}
+
+impl<R: BufRead> Merged<R> {
+    pub(crate) fn new(readers: Vec<Reader<R>>) -> Self {
+        let header = readers
+            .first()
+            .map(|r| r.header().clone())
+            .unwrap_or_default();
+        Self {
+            header,
+            inputs: readers.into_iter().map(Iterator::peekable).collect(),
+        }
+    }
+
+    pub(crate) fn inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Header of the first input, which defines the merge order.
+    pub(crate) fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl<R: BufRead> Iterator for Merged<R> {
+    type Item = Result<(usize, Alignment), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut best: Option<(usize, (usize, Option<Position>))> = None;
+        for (i, input) in self.inputs.iter_mut().enumerate() {
+            let key = match input.peek() {
+                None => continue,
+                Some(Ok(alignment)) => (
+                    self.header
+                        .reference_seq_index(alignment.ref_seq_name())
+                        .unwrap_or(usize::MAX),
+                    alignment.position(),
+                ),
+                // Report errors as soon as they are seen
+                Some(Err(_)) => {
+                    best = Some((i, (0, None)));
+                    break;
+                }
+            };
+            if best.is_none_or(|(_, best_key)| key < best_key) {
+                best = Some((i, key));
+            }
+        }
+        let (i, _) = best?;
+        self.inputs[i].next().map(|r| r.map(|a| (i, a)))
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    alignment::{Alignment, position::Position},
+    header::{AlignmentGrouping, Header, SortOrder, parser::ParseError},
+};
+
+/// Compares read names in the natural order of `samtools sort -n`: runs of digits compare by
+/// their numeric value, everything else byte by byte.
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let digits = |s: &[u8], start: usize| {
+                let zeros = s[start..].iter().take_while(|&&c| c == b'0').count();
+                let len = s[start + zeros..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                (start + zeros, len)
+            };
+            let (start_a, len_a) = digits(a, i);
+            let (start_b, len_b) = digits(b, j);
+            // Without leading zeros, a longer number is larger
+            let order = len_a
+                .cmp(&len_b)
+                .then_with(|| a[start_a..start_a + len_a].cmp(&b[start_b..start_b + len_b]))
+                // Equal values: fewer leading zeros first
+                .then_with(|| (start_a - i).cmp(&(start_b - j)));
+            if order != Ordering::Equal {
+                return order;
+            }
+            i = start_a + len_a;
+            j = start_b + len_b;
+        } else {
+            match a[i].cmp(&b[j]) {
+                Ordering::Equal => {}
+                order => return order,
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+/// The first record found out of the order declared by the header.
+#[derive(Debug, Clone)]
+pub(crate) struct Violation {
+    /// 1-based number of the record among all records
+    pub(crate) record: u64,
+    pub(crate) query_name: String,
+    /// The rule it breaks: `coordinate`, `queryname`, `query grouping` or `reference grouping`
+    pub(crate) rule: &'static str,
+}
+
+/// What is kept of the previous record to check the next one against.
+struct Previous {
+    query_name: String,
+    ref_seq_name: String,
+    coordinate: (usize, Option<Position>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    None,
+    Coordinate,
+    NaturalName,
+    LexicographicalName,
+}
+
+/// Passes records through, checking them against the SO and GO of the header.
+///
+/// The first record out of order is returned as `ParseError::Unsorted`, with the details in
+/// [`OrderChecked::violation`]; later records are passed on unchecked.
+///
+/// Checking a grouping the sort order does not already imply keeps every finished group: for
+/// `GO:query` without `SO:queryname` that is one entry per read name in the file.
+pub(crate) struct OrderChecked<I> {
+    inner: I,
+    // Index of each @SQ line, the only part of the header coordinate order needs
+    reference_indices: HashMap<String, usize>,
+    order: Order,
+    grouping: AlignmentGrouping,
+    record: u64,
+    previous: Option<Previous>,
+    // Names or references whose records have all been seen, when checking grouping
+    finished: HashSet<String>,
+    violation: Option<Violation>,
+}
+
+impl<I: Iterator<Item = Result<Alignment, ParseError>>> OrderChecked<I> {
+    pub(crate) fn new(inner: I, header: &Header) -> Self {
+        let order = match header.sort_order() {
+            Some(SortOrder::Coordinate) => Order::Coordinate,
+            // SS refines the order of names, which is natural unless stated otherwise
+            Some(SortOrder::QueryName) => match header.sub_sorting() {
+                Some("queryname:lexicographical") => Order::LexicographicalName,
+                _ => Order::NaturalName,
+            },
+            _ => Order::None,
+        };
+        // Records sorted by name are grouped by name, and sorted by coordinate grouped by
+        // reference, so only other combinations need tracking
+        let grouping = match (order, header.grouping()) {
+            (Order::NaturalName | Order::LexicographicalName, Some(AlignmentGrouping::Query))
+            | (Order::Coordinate, Some(AlignmentGrouping::Reference))
+            | (_, None) => AlignmentGrouping::None,
+            (_, Some(grouping)) => grouping.clone(),
+        };
+        Self {
+            inner,
+            reference_indices: header
+                .reference_seqs()
+                .enumerate()
+                .map(|(i, ref_seq)| (ref_seq.name().to_owned(), i))
+                .collect(),
+            order,
+            grouping,
+            record: 0,
+            previous: None,
+            finished: HashSet::new(),
+            violation: None,
+        }
+    }
+
+    pub(crate) fn violation(&self) -> Option<&Violation> {
+        self.violation.as_ref()
+    }
+
+    /// Coordinate key of a record: reference index, then position, with unplaced records last.
+    fn coordinate(&self, alignment: &Alignment) -> Result<(usize, Option<Position>), ParseError> {
+        match alignment.reference_name() {
+            None => Ok((usize::MAX, None)),
+            Some(name) => self
+                .reference_indices
+                .get(name)
+                .map(|&i| (i, alignment.position()))
+                .ok_or(ParseError::UnknownReference),
+        }
+    }
+
+    /// The rule `alignment` breaks by following the previous record, if any.
+    fn check(&mut self, alignment: &Alignment) -> Result<Option<&'static str>, ParseError> {
+        let coordinate = match self.order {
+            Order::Coordinate => self.coordinate(alignment)?,
+            _ => (0, None),
+        };
+        let current = Previous {
+            query_name: alignment.query_name().to_owned(),
+            ref_seq_name: alignment.ref_seq_name().to_owned(),
+            coordinate,
+        };
+        let Some(previous) = self.previous.replace(current) else {
+            return Ok(None);
+        };
+        let in_order = match self.order {
+            Order::None => true,
+            Order::Coordinate => previous.coordinate <= coordinate,
+            Order::NaturalName => {
+                natural_cmp(&previous.query_name, alignment.query_name()) != Ordering::Greater
+            }
+            Order::LexicographicalName => previous.query_name.as_str() <= alignment.query_name(),
+        };
+        if !in_order {
+            return Ok(Some(match self.order {
+                Order::Coordinate => "coordinate",
+                _ => "queryname",
+            }));
+        }
+
+        let (previous_key, key, rule) = match self.grouping {
+            AlignmentGrouping::None => return Ok(None),
+            AlignmentGrouping::Query => (
+                previous.query_name,
+                alignment.query_name(),
+                "query grouping",
+            ),
+            AlignmentGrouping::Reference => (
+                previous.ref_seq_name,
+                alignment.ref_seq_name(),
+                "reference grouping",
+            ),
+        };
+        if previous_key != key {
+            self.finished.insert(previous_key);
+            if self.finished.contains(key) {
+                return Ok(Some(rule));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<I: Iterator<Item = Result<Alignment, ParseError>>> Iterator for OrderChecked<I> {
+    type Item = Result<Alignment, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let alignment = match self.inner.next()? {
+            Ok(alignment) => alignment,
+            Err(e) => return Some(Err(e)),
+        };
+        self.record += 1;
+        if self.violation.is_some() {
+            return Some(Ok(alignment));
+        }
+        match self.check(&alignment) {
+            Ok(None) => {}
+            Ok(Some(rule)) => {
+                self.violation = Some(Violation {
+                    record: self.record,
+                    query_name: alignment.query_name().to_owned(),
+                    rule,
+                });
+                return Some(Err(ParseError::Unsorted));
+            }
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(alignment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_digit_runs_by_value() {
+        assert_eq!(natural_cmp("r2", "r10"), Ordering::Less);
+        assert_eq!(natural_cmp("r10", "r9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1b20", "a1b3"), Ordering::Greater);
+        assert_eq!(natural_cmp("r:12:7", "r:12:7"), Ordering::Equal);
+        assert_eq!(
+            natural_cmp("99999999999999999999999", "100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn puts_fewer_leading_zeros_first() {
+        assert_eq!(natural_cmp("r7", "r007"), Ordering::Less);
+        assert_eq!(natural_cmp("r007", "r8"), Ordering::Less);
+        assert_eq!(natural_cmp("r0", "r00"), Ordering::Less);
+        assert_eq!(natural_cmp("r00x", "r0y"), Ordering::Greater);
+    }
+
+    #[test]
+    fn compares_other_bytes_and_prefixes() {
+        assert_eq!(natural_cmp("a", "b"), Ordering::Less);
+        assert_eq!(natural_cmp("B", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("r1", "r1a"), Ordering::Less);
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("1a", "a"), Ordering::Less);
+    }
+
+    #[test]
+    fn sorts_names_naturally() {
+        let mut names = ["r10", "r2", "r02", "q5", "r1b", "r1", "r100"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["q5", "r1", "r1b", "r2", "r02", "r10", "r100"]);
+    }
+}
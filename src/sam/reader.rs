@@ -0,0 +1,79 @@
+use std::io::BufRead;
+
+use crate::{
+    alignment::{Alignment, reader::Alignments},
+    header::{Header, parser::ParseError, provenance::ProgramGraph, reader::read_header},
+    sam::validation::{Stringency, validate},
+};
+
+/// Reads a SAM stream: the header up front, then alignments on demand.
+pub(crate) struct Reader<R> {
+    header: Header,
+    alignments: Alignments<R>,
+    stringency: Stringency,
+    // 1-based number of the last record read, to locate errors
+    record: u64,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub(crate) fn new(mut reader: R) -> Result<Self, ParseError> {
+        let header = read_header(&mut reader)?;
+        Ok(Self {
+            header,
+            alignments: Alignments::new(reader),
+            stringency: Stringency::Silent,
+            record: 0,
+        })
+    }
+
+    /// Checks records against the header from now on, and with `Strict` the header itself:
+    /// its @PG chains and the PG, FO and KS fields of its @RG lines.
+    pub(crate) fn set_stringency(&mut self, stringency: Stringency) -> Result<(), ParseError> {
+        if stringency == Stringency::Strict {
+            ProgramGraph::new(&self.header)?;
+            self.header.check_read_groups()?;
+        }
+        self.stringency = stringency;
+        Ok(())
+    }
+
+    pub(crate) fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Alignment, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.alignments.next()?;
+        self.record += 1;
+        let alignment = match record {
+            Ok(alignment) => alignment,
+            Err(e) => {
+                return Some(Err(ParseError::BadRecord {
+                    record: self.record,
+                    error: Box::new(e),
+                }));
+            }
+        };
+        if self.stringency == Stringency::Silent {
+            return Some(Ok(alignment));
+        }
+        for problem in validate(&alignment, &self.header) {
+            if self.stringency == Stringency::Strict {
+                return Some(Err(ParseError::InvalidRecord {
+                    record: self.record,
+                    query_name: alignment.query_name().into(),
+                    problem: problem.to_string(),
+                }));
+            }
+            eprintln!(
+                "samovar: warning: record {} ({}): {problem}",
+                self.record,
+                alignment.query_name()
+            );
+        }
+        Some(Ok(alignment))
+    }
+}
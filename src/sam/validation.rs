@@ -0,0 +1,130 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    alignment::{Alignment, flag::FlagProblem},
+    header::{Header, parser::ParseError},
+};
+
+/// How records that do not agree with the header are handled while reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stringency {
+    /// The first problem is an error
+    Strict,
+    /// Problems are reported on stderr and the records kept
+    Lenient,
+    /// Records are not checked
+    Silent,
+}
+
+impl FromStr for Stringency {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            "silent" => Ok(Self::Silent),
+            _ => Err(ParseError::UnknownValue),
+        }
+    }
+}
+
+/// A way a record disagrees with the header, with the value at fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Problem {
+    /// RNAME is not an @SQ line
+    UnknownReference(String),
+    /// RNEXT is not an @SQ line
+    UnknownMateReference(String),
+    /// The alignment ends past the LN of its reference
+    PastReferenceEnd(u32),
+    /// PNEXT is past the LN of the mate reference
+    MatePastReferenceEnd(u32),
+    /// The RG tag is not an @RG line
+    UnknownReadGroup(String),
+    /// The PG tag is not a @PG line
+    UnknownProgram(String),
+    /// FLAG combines bits the specification rules out
+    InvalidFlag(FlagProblem),
+}
+
+impl Problem {
+    /// Upper-case name of the problem, as in ValidateSamFile summaries.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::UnknownReference(_) => "UNKNOWN_REFERENCE",
+            Self::UnknownMateReference(_) => "UNKNOWN_MATE_REFERENCE",
+            Self::PastReferenceEnd(_) => "PAST_REFERENCE_END",
+            Self::MatePastReferenceEnd(_) => "MATE_PAST_REFERENCE_END",
+            Self::UnknownReadGroup(_) => "UNKNOWN_READ_GROUP",
+            Self::UnknownProgram(_) => "UNKNOWN_PROGRAM",
+            Self::InvalidFlag(problem) => problem.name(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownReference(name) => write!(f, "RNAME {name} is not in the header"),
+            Self::UnknownMateReference(name) => write!(f, "RNEXT {name} is not in the header"),
+            Self::PastReferenceEnd(end) => write!(f, "alignment ends past its reference at {end}"),
+            Self::MatePastReferenceEnd(pnext) => {
+                write!(f, "PNEXT {pnext} is past the end of the mate reference")
+            }
+            Self::UnknownReadGroup(id) => write!(f, "RG {id} is not in the header"),
+            Self::UnknownProgram(id) => write!(f, "PG {id} is not in the header"),
+            Self::InvalidFlag(problem) => write!(f, "invalid FLAG: {}", problem.name()),
+        }
+    }
+}
+
+/// Checks a record against the @SQ, @RG and @PG lines of the header, and its flag against the
+/// rules of the specification.
+pub(crate) fn validate(alignment: &Alignment, header: &Header) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for problem in alignment.flag().problems() {
+        problems.push(Problem::InvalidFlag(problem));
+    }
+    if let Some(name) = alignment.reference_name() {
+        match header.reference_seq(name) {
+            None => problems.push(Problem::UnknownReference(name.into())),
+            Some(ref_seq) => {
+                // Unmapped reads placed at their mate's position have no span of their own
+                let end = if alignment.flag().is_unmapped() {
+                    alignment.position()
+                } else {
+                    alignment.end()
+                };
+                if let Some(end) = end.filter(|end| u64::from(end.get()) > ref_seq.length()) {
+                    problems.push(Problem::PastReferenceEnd(end.get()));
+                }
+            }
+        }
+    }
+    if let Some(name) = alignment.mate_reference_name() {
+        match header.reference_seq(name) {
+            // Reported once for the record's own reference
+            None if alignment.rnext() == "=" => {}
+            None => problems.push(Problem::UnknownMateReference(name.into())),
+            Some(ref_seq) => {
+                if let Some(pnext) = alignment.mate_position()
+                    && u64::from(pnext.get()) > ref_seq.length()
+                {
+                    problems.push(Problem::MatePastReferenceEnd(pnext.get()));
+                }
+            }
+        }
+    }
+    if let Some(id) = alignment.tag(b"RG").and_then(|t| t.value.as_str())
+        && header.read_group(id).is_none()
+    {
+        problems.push(Problem::UnknownReadGroup(id.into()));
+    }
+    if let Some(id) = alignment.tag(b"PG").and_then(|t| t.value.as_str())
+        && header.program(id).is_none()
+    {
+        problems.push(Problem::UnknownProgram(id.into()));
+    }
+    problems
+}